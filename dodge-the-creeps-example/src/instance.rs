@@ -10,14 +10,24 @@ pub struct DodgeTheCreepsInstance {
 impl DodgeTheCreepsInstance {
     fn _init(owner: Node) -> Self {
         let mut instance = DodgeTheCreepsInstance { engine: VermarineEngine::<Renderables>::new(owner) };
+
+        // Start recording or replaying if --record <path> or --replay <path> was passed
+        if let Err(error) = instance.engine.configure_from_env() {
+            godot_print!("Could not start the replay: {}", error);
+        }
         
         // Add resources
         let renderables = Models::<Renderables>::default();
         instance.engine.resources.insert(load_renderables(renderables));
+
+        // Move with the arrow keys, the left stick of any gamepad or by dragging on a touch screen
+        let movement = VirtualStick::new()
+            .with_actions("move_left", "move_right", "move_up", "move_down")
+            .with_joy_axes(0, 1)
+            .with_touch_drag(100f32);
+        instance.engine.resources.get_mut::<InputState>().unwrap().define_stick("move", movement);
         
-        instance.engine.push(Box::new(MainState {
-            executor: Executor::new(create_systems()),
-        }));
+        instance.engine.push(Box::new(MainState { }));
 
         instance
     }
@@ -32,6 +42,16 @@ impl DodgeTheCreepsInstance {
     fn _physics_process(&mut self, owner: Node, delta: f64) {
         self.engine._physics_process(owner, delta);
     }
+
+    #[export]
+    fn _notification(&mut self, owner: Node, what: i64) {
+        self.engine._notification(owner, what);
+    }
+
+    #[export]
+    fn _input(&mut self, owner: Node, event: Option<InputEvent>) {
+        self.engine._input(owner, event);
+    }
 }
 
 // Function that registers all exposed classes to Godot
@@ -1,8 +1,6 @@
 use crate::prelude::*;
 
-pub struct MainState {
-    pub executor: Executor,
-}
+pub struct MainState { }
 
 impl State for MainState {
     fn on_push(&mut self, data: &mut StateData, resources: &mut Resources) {
@@ -15,7 +13,7 @@ impl State for MainState {
             (0..1).map(|_| (
                 Renderable::new(Position::default(), player.1, player.0),
                 Position::new(240f32, 450f32), 
-                TakesInput { speed: 400f32 / 60f32 }, 
+                TakesInput { speed: 400f32 }, 
                 Velocity::default(),
                 Collider { width: 25.0, height: 25.0, offset_x: 0.0, offset_y: -2.5},
                 PlayerComp { },
@@ -23,7 +21,11 @@ impl State for MainState {
         );
     }
 
-    fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
-        self.executor.execute(&mut data.world, resources);
+    fn schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> {
+        Some(StateSchedule::new(create_systems()))
+    }
+
+    fn get_name(&mut self, _: &mut StateData, _: &mut Resources) -> String {
+        "Main".into()
     }
 }
\ No newline at end of file
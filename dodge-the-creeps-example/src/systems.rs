@@ -13,36 +13,15 @@ pub(crate) fn input_system() -> Box<dyn Schedulable> {
     type Query1 = (Read<TakesInput>, Write<Velocity>);
     type Query2 = (Read<TakesInput>, Read<Velocity>, Write<Renderable>);
     SystemBuilder::<()>::new("InputSystem")
+        .read_resource::<InputState>()
         .write_component::<Renderable>()
         .with_query(<Query1>::query())
         .with_query(<Query2>::query())
-        .build(move |_commands, world, _resource, queries| {
+        .build(move |_commands, world, input, queries| {
             for (takes_input, mut vel) in queries.0.iter_mut(&mut *world) {
-                vel.x = 0f32;
-                vel.y = 0f32;
-
-                let mut i_x = 0i32;
-                let mut i_y = 0i32;
-                for (key, x, y) in [
-                    ("move_left", -1, 0),
-                    ("move_right", 1, 0),
-                    ("move_up", 0, -1),
-                    ("move_down", 0, 1),
-                ].iter() {
-                    if Input::godot_singleton().is_action_pressed(GodotString::from(key)) {
-                        i_x += x;
-                        i_y += y;
-                        vel.x += *x as f32;
-                        vel.y += *y as f32;
-                    }
-                }
-
-                if vel.x != 0f32 || vel.y != 0f32 {
-                    let vec = euclid::Vector2D::<f32, euclid::UnknownUnit>::new(vel.x as f32, vel.y as f32);
-                    let vec = vec.normalize() * takes_input.speed;
-                    vel.x = vec.x;
-                    vel.y = vec.y;
-                }
+                let movement = input.stick("move") * takes_input.speed;
+                vel.x = movement.x;
+                vel.y = movement.y;
             }
 
             for (_, vel, mut renderable) in queries.1.iter_mut(&mut *world) {
@@ -66,27 +45,33 @@ pub(crate) fn input_system() -> Box<dyn Schedulable> {
 
 pub(crate) fn move_system() -> Box<dyn Schedulable> {
     SystemBuilder::<()>::new("MoveSystem")
+        .read_resource::<Time>()
         .with_query(<(Write<Position>, Read<Velocity>)>::query())
-        .build(move |_commands, world, _resource, queries| {
+        .build(move |_commands, world, time, queries| {
+            let delta = time.delta() as f32;
             for (_entity, (mut pos, vel)) in queries.iter_entities_mut(&mut *world) {
                 let rot = euclid::Rotation2D::<f32, euclid::UnknownUnit, euclid::UnknownUnit>::new(pos.rotation);
                 let vel = rot.transform_vector(euclid::Vector2D::<f32, euclid::UnknownUnit>::new(vel.x, vel.y));
-                pos.x += vel.x;
-                pos.y += vel.y;
+                pos.x += vel.x * delta;
+                pos.y += vel.y * delta;
             } 
         })
 }
 
 pub(crate) fn spawn_enemy_system() -> Box<dyn Schedulable> {
-    let mut counter: i32 = 1;
+    // Seconds between each enemy spawn
+    const SPAWN_INTERVAL: f64 = 0.5;
+    let mut timer: f64 = 0f64;
     SystemBuilder::<()>::new("SpawnEnemySystem")
+        .read_resource::<Time>()
         .write_resource::<Models<Renderables>>()
-        .build(move |commands, _world, resource, _queries| {
-            if counter % 30 == 0 {
-                counter = 0;
+        .write_resource::<Random>()
+        .build(move |commands, _world, (time, models, rand), _queries| {
+            timer += time.delta();
+            if timer >= SPAWN_INTERVAL {
+                timer -= SPAWN_INTERVAL;
 
-                let mut enemy = resource.data_from_t(&Renderables::Creatures(CreatureRenderables::Enemy)).unwrap();
-                let mut rand = rand::thread_rng();
+                let mut enemy = models.data_from_t(&Renderables::Creatures(CreatureRenderables::Enemy)).unwrap();
 
                 let anim = rand.gen_range(0, 3);
                 if let Template::ASprite(mut a_sprite) = enemy.0 {
@@ -100,47 +85,38 @@ pub(crate) fn spawn_enemy_system() -> Box<dyn Schedulable> {
                     enemy.0 = Template::ASprite(a_sprite);
                 }
 
+                let mut position = if rand.gen() {
+                    // Spawn horizontal
+                    let y = (rand.gen_range(0, 2) * 719) as f32;
+                    let x = rand.gen_range(0, 480) as f32;
+                    Position::new(x, y)
+                } else {
+                    // Spawn vertical
+                    let x = (rand.gen_range(0, 2) * 479) as f32;
+                    let y = rand.gen_range(0, 720) as f32;
+                    Position::new(x, y)
+                };
+
+                let new_angle = euclid::Vector2D::<f32, euclid::UnknownUnit>::new(position.x - 240f32, position.y - 360f32).angle_from_x_axis() + euclid::Angle::pi();
+                let variance = if rand.gen() {
+                    euclid::Angle::radians(-0.3f32)
+                } else {
+                    euclid::Angle::radians(0.3f32)
+                };
+                position.rotation = new_angle + variance;
+                let speed = (rand.gen::<f32>() * 102f32) + 150f32;
+
                 commands.insert(
                     (),
-                    (0..1).map(move |_| {
-                        let mut position = if rand::random() {
-                            // Spawn horizontal
-                            let y = (rand.gen_range(0, 2) * 719) as f32;
-                            let x = rand.gen_range(0, 480) as f32;
-                            Position {
-                                x: x,
-                                y: y,
-                                rotation: euclid::Angle::radians(0f32),
-                            }
-                        } else {
-                            // Spawn vertical
-                            let x = (rand.gen_range(0, 2) * 479) as f32;
-                            let y = rand.gen_range(0, 720) as f32;
-                            Position {
-                                x: x,
-                                y: y,
-                                rotation: euclid::Angle::radians(0f32),
-                            }
-                        };
-
-                        let new_angle = euclid::Vector2D::<f32, euclid::UnknownUnit>::new(position.x - 240f32, position.y - 360f32).angle_from_x_axis() + euclid::Angle::pi();
-                        let variance = if rand::random() {
-                            euclid::Angle::radians(-0.3f32)
-                        } else {
-                            euclid::Angle::radians(0.3f32)
-                        };
-                        position.rotation = new_angle + variance;
-                        
-                        return (
+                    (0..1).map(move |_| (
                         EnemyComp { },
                         Renderable::new(Position::default(), enemy.1, enemy.0),
                         position, 
-                        Velocity { x: (rand.gen::<f32>() * 1.7f32) + 2.5f32, y: 0f32 },
+                        Velocity { x: speed, y: 0f32 },
                         Collider { width: 12.0, height: 12.0, offset_x: 14.0, offset_y: 0.0 },
-                    )}
-                ));
+                    ))
+                );
             }
-            counter+=1;
         })
 } 
 
@@ -40,6 +40,18 @@ impl HelloWorld {
     fn _physics_process(&mut self, owner: Node, delta: f64) {
         self.engine._physics_process(owner, delta);
     }
+
+    // Window close requests are passed to the engine so that our states can handle them
+    #[export]
+    fn _notification(&mut self, owner: Node, what: i64) {
+        self.engine._notification(owner, what);
+    }
+
+    // Mouse and text input reach the InputState resource through _input
+    #[export]
+    fn _input(&mut self, owner: Node, event: Option<InputEvent>) {
+        self.engine._input(owner, event);
+    }
 }
 
 // Function that registers all exposed classes to Godot
@@ -77,11 +89,11 @@ impl State for YourState {
         godot_print!("{}", self.count);
         self.count += 1;
 
-        if Input::godot_singleton().is_action_just_pressed(GodotString::from("pause")) {
+        if resources.get::<InputState>().unwrap().is_action_just_pressed("pause") {
             // Get the TransResource that allows us to send state transitions to the engine
             let sender = resources.get::<TransResource>().unwrap();
             // Send a closure that creates the Trans we want to execute
-            sender.trans.try_send(Box::from(|| Trans::Push(Box::new(PauseState { })))).ok();
+            sender.send(|| Trans::Push(Box::new(PauseState { }))).unwrap();
         }
     }
 }
@@ -89,6 +101,16 @@ impl State for YourState {
 pub struct PauseState { }
 
 impl State for PauseState {
+    // Stop YourState from running while the game is paused
+    fn cover_policy(&mut self, _data: &mut StateData, _resources: &mut Resources) -> CoverPolicy {
+        CoverPolicy::Freeze
+    }
+
+    // Stop the states below from seeing the pause key while the game is paused
+    fn blocks_input(&mut self, _data: &mut StateData, _resources: &mut Resources) -> bool {
+        true
+    }
+
     fn on_push(&mut self, data: &mut StateData, resources: &mut Resources) {
         // Retrieve our data from Models<T>
         let models = resources.get::<Models<i32>>().unwrap();
@@ -105,11 +127,11 @@ impl State for PauseState {
     }
 
     fn update(&mut self, _data: &mut StateData, resources: &mut Resources) {
-        if Input::godot_singleton().is_action_just_pressed(GodotString::from("pause")) {
+        if resources.get::<InputState>().unwrap().is_action_just_pressed("pause") {
             // Get the TransResource that allows us to send state transitions to the engine
             let sender = resources.get::<TransResource>().unwrap();
             // Send a closure that creates the Trans we want to execute
-            sender.trans.try_send(Box::from(|| Trans::Pop)).ok();
+            sender.send(|| Trans::Pop).unwrap();
         }
     }
 }
\ No newline at end of file
@@ -28,6 +28,16 @@ impl SceneExampleInstance {
     fn _physics_process(&mut self, owner: Node, delta: f64) {
         self.engine._physics_process(owner, delta);
     }
+
+    #[export]
+    fn _notification(&mut self, owner: Node, what: i64) {
+        self.engine._notification(owner, what);
+    }
+
+    #[export]
+    fn _input(&mut self, owner: Node, event: Option<InputEvent>) {
+        self.engine._input(owner, event);
+    }
 }
 
 // Function that registers all exposed classes to Godot
@@ -20,10 +20,10 @@ impl State for BaseState {
             unsafe {
                 // Call shadow update on gdscript
                 // This sets the text to current display string
-                data.statenode.unwrap().call("_shadow_update".into(), &[Variant::from_godot_string(&gdstring)]);
+                data.statenode.unwrap().godot().unwrap().call("_shadow_update".into(), &[Variant::from_godot_string(&gdstring)]);
 
                 // Get the current input string and update res.input if there is one
-                let returned = data.statenode.unwrap().call("_get_text_input".into(), &[Variant::new(); 0]);
+                let returned = data.statenode.unwrap().godot().unwrap().call("_get_text_input".into(), &[Variant::new(); 0]);
                 if let Some(string) = returned.try_to_godot_string() {
                     res.input = string.to_string();
                 }
@@ -32,7 +32,7 @@ impl State for BaseState {
 
         // Pop the stack if pop button is pressed
         unsafe {
-            let returned = data.statenode.unwrap().call("_get_pop_input".into(), &[Variant::new(); 0]);
+            let returned = data.statenode.unwrap().godot().unwrap().call("_get_pop_input".into(), &[Variant::new(); 0]);
             if let Some(b) = returned.try_to_bool() {
                 resources.insert(PopInput { inner: b } );
            }
@@ -42,7 +42,7 @@ impl State for BaseState {
     fn on_push(&mut self, _data: &mut StateData, resources: &mut Resources) {
         // Add a base printer
         let sender = resources.get::<TransResource>().unwrap();
-        sender.trans.try_send(Box::from(|| Trans::Push(Box::new(PrintState { output: "Bottom of stack".into() })))).ok();
+        sender.send(|| Trans::Push(Box::new(PrintState { output: "Bottom of stack".into() }))).unwrap();
     }
 
     fn is_node(&mut self, _data: &mut StateData, resources: &mut Resources) -> Option<usize> {
@@ -67,7 +67,7 @@ impl State for PrintState {
         if let Some(res) = resources.get::<TextResource>() {
             if res.input != "" {
                 let blah = res.input.clone();
-                sender.trans.try_send(Box::from(move || Trans::Push(Box::new(PrintState { output: blah })))).ok();
+                sender.send(move || Trans::Push(Box::new(PrintState { output: blah }))).unwrap();
             }
         }
     }
@@ -76,7 +76,7 @@ impl State for PrintState {
         if let Some(pop_input) = resources.get::<PopInput>() {
             if pop_input.inner {
                 let sender = resources.get::<TransResource>().unwrap();
-                sender.trans.try_send(Box::from(|| Trans::Pop)).ok();
+                sender.send(|| Trans::Pop).unwrap();
             }
         }
         resources.insert(PopInput { inner: false });
@@ -0,0 +1,245 @@
+use crate::prelude::*;
+
+/// Something that can drive a virtual axis, every source gives a value from -1 to 1
+#[derive(Clone, Debug, PartialEq)]
+pub enum AxisSource {
+    /// The negative action pulls the axis towards -1 and the positive action pulls it towards 1, analog actions give values in between
+    Actions(String, String),
+    /// A gamepad analog axis by godot axis index, the axis of every connected gamepad is read and the one pushed furthest is used
+    JoyAxis(i64),
+    /// How far the touch has been dragged horizontally, dragging by the given number of pixels gives a value of 1.
+    /// Distances under one pixel are read as one pixel
+    TouchDragX(f32),
+    /// How far the touch has been dragged vertically, dragging by the given number of pixels gives a value of 1.
+    /// Distances under one pixel are read as one pixel
+    TouchDragY(f32),
+}
+
+/// The shortest drag distance a touch source divides by, this keeps a distance of 0 from giving infinite values
+const MIN_TOUCH_DRAG: f32 = 1f32;
+
+impl AxisSource {
+    pub(crate) fn value(&self, input: &InputState) -> f32 {
+        let value = match self {
+            AxisSource::Actions(negative, positive) => input.get_axis(negative, positive),
+            AxisSource::JoyAxis(axis) => input.get_joy_axis_any(*axis),
+            AxisSource::TouchDragX(distance) => input.touch_drag().map_or(0f32, |drag| drag.x / distance.max(MIN_TOUCH_DRAG)),
+            AxisSource::TouchDragY(distance) => input.touch_drag().map_or(0f32, |drag| drag.y / distance.max(MIN_TOUCH_DRAG)),
+        };
+        value.max(-1f32).min(1f32)
+    }
+}
+
+/// A named axis from -1 to 1 that combines several sources, the source pushed furthest wins
+/// ```
+/// let steer = VirtualAxis::new()
+///     .with_actions("steer_left", "steer_right")
+///     .with_joy_axis(0)
+///     .with_deadzone(0.2);
+/// resources.get_mut::<InputState>().unwrap().define_axis("steer", steer);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualAxis {
+    pub sources: Vec<AxisSource>,
+    /// Values closer to 0 than this are read as 0, values outside of it are rescaled so the axis still goes from 0 to 1
+    pub deadzone: f32,
+}
+
+impl Default for VirtualAxis {
+    fn default() -> Self {
+        VirtualAxis {
+            sources: vec![],
+            deadzone: 0.2,
+        }
+    }
+}
+
+impl VirtualAxis {
+    pub fn new() -> Self {
+        VirtualAxis::default()
+    }
+
+    pub fn with_source(mut self, source: AxisSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn with_actions(self, negative: &str, positive: &str) -> Self {
+        self.with_source(AxisSource::Actions(negative.into(), positive.into()))
+    }
+
+    pub fn with_joy_axis(self, axis: i64) -> Self {
+        self.with_source(AxisSource::JoyAxis(axis))
+    }
+
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone.max(0f32).min(0.99f32);
+        self
+    }
+
+    pub(crate) fn value(&self, input: &InputState) -> f32 {
+        let value = strongest(self.sources.iter().map(|source| source.value(input)));
+        if value.abs() <= self.deadzone {
+            return 0f32;
+        }
+        value.signum() * (value.abs() - self.deadzone) / (1f32 - self.deadzone)
+    }
+}
+
+/// A named 2D stick that combines several sources for each direction, the result is never longer than 1
+/// ```
+/// let movement = VirtualStick::new()
+///     .with_actions("move_left", "move_right", "move_up", "move_down")
+///     .with_joy_axes(0, 1)
+///     .with_touch_drag(100f32);
+/// resources.get_mut::<InputState>().unwrap().define_stick("move", movement);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VirtualStick {
+    pub x: Vec<AxisSource>,
+    pub y: Vec<AxisSource>,
+    /// Sticks shorter than this are read as 0, longer sticks are rescaled so the stick still goes from 0 to 1.
+    /// The deadzone is applied to the length of the stick rather than to each axis so diagonals aren't snapped
+    pub deadzone: f32,
+}
+
+impl Default for VirtualStick {
+    fn default() -> Self {
+        VirtualStick {
+            x: vec![],
+            y: vec![],
+            deadzone: 0.2,
+        }
+    }
+}
+
+impl VirtualStick {
+    pub fn new() -> Self {
+        VirtualStick::default()
+    }
+
+    pub fn with_sources(mut self, x: AxisSource, y: AxisSource) -> Self {
+        self.x.push(x);
+        self.y.push(y);
+        self
+    }
+
+    /// Up is negative y the same as it is in godot
+    pub fn with_actions(self, left: &str, right: &str, up: &str, down: &str) -> Self {
+        self.with_sources(AxisSource::Actions(left.into(), right.into()), AxisSource::Actions(up.into(), down.into()))
+    }
+
+    /// Godot's left stick is axes 0 and 1 and the right stick is axes 2 and 3
+    pub fn with_joy_axes(self, x: i64, y: i64) -> Self {
+        self.with_sources(AxisSource::JoyAxis(x), AxisSource::JoyAxis(y))
+    }
+
+    /// Dragging a touch by distance pixels pushes the stick all the way, distances under one pixel are read as one pixel
+    pub fn with_touch_drag(self, distance: f32) -> Self {
+        self.with_sources(AxisSource::TouchDragX(distance), AxisSource::TouchDragY(distance))
+    }
+
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone.max(0f32).min(0.99f32);
+        self
+    }
+
+    pub(crate) fn value(&self, input: &InputState) -> Vector2 {
+        let stick = Vector2::new(
+            strongest(self.x.iter().map(|source| source.value(input))),
+            strongest(self.y.iter().map(|source| source.value(input))),
+        );
+
+        let length = stick.length();
+        if length <= self.deadzone {
+            return Vector2::new(0f32, 0f32);
+        }
+        let scaled = ((length - self.deadzone) / (1f32 - self.deadzone)).min(1f32);
+        stick / length * scaled
+    }
+}
+
+/// A gamepad being plugged in or unplugged, these are available from InputState::joypad_events() for the frame they happen on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoypadEvent {
+    Connected(i64),
+    Disconnected(i64),
+}
+
+/// Returns the value furthest from 0
+fn strongest<I>(values: I) -> f32
+    where I: Iterator<Item = f32> {
+    values.fold(0f32, |strongest, value| if value.abs() > strongest.abs() { value } else { strongest })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn axes_rescale_values_outside_of_the_deadzone() {
+        let mut input = InputState::default();
+        input.define_axis("steer", VirtualAxis::new().with_actions("left", "right").with_deadzone(0.2));
+
+        input.set_action_strength("right", 0.1f32);
+        assert_eq!(input.axis("steer"), 0f32);
+        input.set_action_strength("right", 0.6f32);
+        assert!(close(input.axis("steer"), 0.5f32));
+        input.set_action_strength("right", 1f32);
+        assert!(close(input.axis("steer"), 1f32));
+        input.release("right");
+        input.set_action_strength("left", 0.6f32);
+        assert!(close(input.axis("steer"), -0.5f32));
+    }
+
+    #[test]
+    fn the_source_pushed_furthest_drives_the_axis() {
+        let mut input = InputState::default();
+        input.define_axis("steer", VirtualAxis::new().with_actions("left", "right").with_joy_axis(0).with_deadzone(0.2));
+        input.set_action_strength("right", 0.6f32);
+        input.set_joy_axis(0, 0, -0.8f32);
+        assert!(close(input.axis("steer"), -0.75f32));
+        assert_eq!(input.axis("missing"), 0f32);
+    }
+
+    #[test]
+    fn stick_deadzones_apply_to_the_length() {
+        let mut input = InputState::default();
+        input.define_stick("move", VirtualStick::new().with_joy_axes(0, 1).with_deadzone(0.2));
+
+        input.set_joy_axis(0, 0, 0.1f32);
+        input.set_joy_axis(0, 1, 0.1f32);
+        assert_eq!(input.stick("move"), Vector2::new(0f32, 0f32));
+
+        input.set_joy_axis(0, 0, 0.3f32);
+        input.set_joy_axis(0, 1, 0f32);
+        let stick = input.stick("move");
+        assert!(close(stick.x, 0.125f32) && close(stick.y, 0f32));
+
+        input.set_joy_axis(0, 0, 1f32);
+        input.set_joy_axis(0, 1, 1f32);
+        assert!(close(input.stick("move").length(), 1f32));
+    }
+
+    #[test]
+    fn touch_drags_push_the_stick_and_a_zero_distance_stays_finite() {
+        let mut input = InputState::default();
+        input.define_stick("move", VirtualStick::new().with_touch_drag(100f32).with_deadzone(0f32));
+        input.define_stick("zero", VirtualStick::new().with_touch_drag(0f32).with_deadzone(0f32));
+        input.begin_touch(Vector2::new(10f32, 10f32));
+        input.drag_touch(Vector2::new(60f32, 10f32));
+
+        let stick = input.stick("move");
+        assert!(close(stick.x, 0.5f32) && close(stick.y, 0f32));
+        let stick = input.stick("zero");
+        assert!(stick.x.is_finite() && stick.y.is_finite());
+        assert!(close(stick.x, 1f32));
+
+        input.drag_touch(Vector2::new(10f32, 10f32));
+        assert_eq!(input.stick("zero"), Vector2::new(0f32, 0f32));
+    }
+}
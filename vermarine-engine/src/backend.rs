@@ -0,0 +1,1007 @@
+use crate::prelude::*;
+use std::any::Any;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+/// A handle to a node owned by a SceneBackend
+///
+/// Handles are cheap to copy and are what the engine stores inside of StateData and Renderable,
+/// they should only ever be passed to the backend that created them.
+#[derive(Clone, Copy)]
+pub enum SceneNode {
+    Godot(Node),
+    Headless(usize),
+}
+
+impl SceneNode {
+    /// Returns the godot node behind this handle if it was created by the GodotBackend
+    pub fn godot(&self) -> Option<Node> {
+        match self {
+            SceneNode::Godot(node) => Some(*node),
+            SceneNode::Headless(_) => None,
+        }
+    }
+
+    /// Returns the index of this handle inside of the HeadlessBackend's node tree if it was created by the HeadlessBackend
+    pub fn headless(&self) -> Option<usize> {
+        match self {
+            SceneNode::Godot(_) => None,
+            SceneNode::Headless(index) => Some(*index),
+        }
+    }
+}
+
+impl PartialEq for SceneNode {
+    fn eq(&self, other: &SceneNode) -> bool {
+        match (self, other) {
+            (SceneNode::Godot(a), SceneNode::Godot(b)) => unsafe { a.get_instance_id() == b.get_instance_id() },
+            (SceneNode::Headless(a), SceneNode::Headless(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Debug for SceneNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SceneNode::Godot(_) => f.write_str("Godot"),
+            SceneNode::Headless(index) => f.write_str(&format!("Headless {}", index)),
+        }
+    }
+}
+
+/// The type of node to create when calling SceneBackend::create_node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind {
+    Node,
+    Node2D,
+    Spatial,
+}
+
+/// The SceneBackend is what the engine uses to build and update the scene tree.
+///
+/// VermarineEngine::new() uses the GodotBackend which creates real godot nodes under the owner node,
+/// VermarineEngine::headless() uses the HeadlessBackend which records a fake node tree in memory so that
+/// states and systems can be run without a godot binary.
+pub trait SceneBackend {
+    /// The node that all state nodes get added to
+    fn root(&self) -> SceneNode;
+
+    /// Creates a new empty node as a child of parent
+    fn create_node(&mut self, parent: SceneNode, name: &str, kind: NodeKind) -> SceneNode;
+
+    /// Instances a model as a child of parent, scene is None if the model was inserted with Models::insert_headless()
+    fn instance_scene(&mut self, parent: SceneNode, name: &str, scene: Option<&PackedScene>, model: usize) -> Option<SceneNode>;
+
+    /// Starts loading a scene in the background, the path works the same way as load_scene().
+    /// Returns a handle to pass to poll_load()
+    fn start_load(&mut self, path: &str) -> Result<usize, String>;
+
+    /// Advances a load started with start_load(), this gets called every frame until the load has finished or failed
+    fn poll_load(&mut self, handle: usize) -> LoadStatus;
+
+    /// Frees a node and all of its children
+    fn free_node(&mut self, node: SceneNode);
+
+    fn get_parent(&self, node: SceneNode) -> Option<SceneNode>;
+
+    /// Moves a node and all of its children to be a child of new_parent, the node keeps its local transform
+    fn reparent(&mut self, node: SceneNode, new_parent: SceneNode);
+
+    /// Moves a node to the end of its parent's children so that it is drawn above its siblings
+    fn raise(&mut self, node: SceneNode);
+
+    fn get_child_count(&self, node: SceneNode) -> usize;
+
+    /// Shows or hides a node and everything below it, including 3D nodes below a 2D node and the other way around
+    fn set_visible(&mut self, node: SceneNode, visible: bool);
+
+    /// Stops a node and its children from processing and playing animations, this is used for frozen states.
+    /// Unpausing only starts back up what was running when the node was paused
+    fn set_paused(&mut self, node: SceneNode, paused: bool);
+
+    fn set_transform(&mut self, node: SceneNode, transform: &Position);
+
+    fn set_transform_3d(&mut self, node: SceneNode, transform: &Position3D);
+
+    /// Applies an AnimSprite template to the AnimatedSprite inside of an instanced model
+    fn set_anim_sprite(&mut self, node: SceneNode, state: &AnimSprite);
+
+    /// Updates which actions are held down, this gets called by the engine at the start of every frame
+    fn poll_input(&mut self, input: &mut InputState);
+
+    /// Records an input event passed to the engine's _input, this is where mouse and text input come from
+    fn input_event(&mut self, event: &InputEvent, input: &mut InputState);
+
+    /// Returns every input action along with what it is bound to
+    fn read_bindings(&self) -> Vec<(String, Vec<Binding>)>;
+
+    /// Replaces the bindings of every action in the list, the GodotBackend leaves any events in the InputMap that a Binding can't represent
+    fn write_bindings(&mut self, bindings: &[(String, Vec<Binding>)]);
+
+    /// The directory that user:// paths point to
+    fn user_dir(&self) -> String;
+
+    /// Prints a message to the backend's console
+    fn print(&mut self, message: &str);
+
+    /// Called from the engine's _ready
+    fn ready(&mut self);
+
+    /// Called once the engine has popped every state after a Trans::Quit
+    fn quit(&mut self);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The number of analog axes godot reads from each gamepad, this is JOY_AXIS_MAX in godot
+const JOY_AXIS_COUNT: i64 = 10;
+
+/// The metadata GodotBackend::set_paused() stores on a frozen node, it holds the FROZE_ flags for what was running before the freeze
+const FROZEN_META: &str = "vermarine_frozen";
+const FROZE_PROCESS: i64 = 1;
+const FROZE_PHYSICS_PROCESS: i64 = 2;
+const FROZE_INPUT: i64 = 4;
+const FROZE_UNHANDLED_INPUT: i64 = 8;
+const FROZE_ANIMATION: i64 = 16;
+
+/// The metadata GodotBackend::set_visible() stores on the nodes it had to hide itself
+const HIDDEN_META: &str = "vermarine_hidden";
+
+/// The two kinds of godot nodes that can be hidden, hiding a node only hides the children of the same kind
+#[derive(Clone, Copy, PartialEq)]
+enum VisualKind {
+    Canvas,
+    Spatial,
+}
+
+/// The default SceneBackend which creates godot nodes as children of the owner node
+pub struct GodotBackend {
+    owner: Node,
+    loaders: Vec<Option<ResourceInteractiveLoader>>,
+}
+
+impl GodotBackend {
+    pub fn new(owner: Node) -> Self {
+        GodotBackend {
+            owner,
+            loaders: vec![],
+        }
+    }
+
+    fn node(node: SceneNode) -> Node {
+        match node.godot() {
+            Some(node) => node,
+            None => panic!("Attempt to use {:?} with the GodotBackend failed", node),
+        }
+    }
+}
+
+impl GodotBackend {
+    /// Keys are bound by scancode, other input events such as mouse motion have no binding
+    fn event_to_binding(event: &InputEvent) -> Option<Binding> {
+        unsafe {
+            if let Some(key) = event.cast::<InputEventKey>() {
+                return Some(Binding::Key(key.get_scancode()));
+            }
+            if let Some(button) = event.cast::<InputEventMouseButton>() {
+                return Some(Binding::MouseButton(button.get_button_index()));
+            }
+            if let Some(button) = event.cast::<InputEventJoypadButton>() {
+                return Some(Binding::JoyButton(button.get_button_index()));
+            }
+            if let Some(motion) = event.cast::<InputEventJoypadMotion>() {
+                return Some(Binding::JoyAxis(motion.get_axis(), motion.get_axis_value() > 0f64));
+            }
+        }
+        None
+    }
+
+    /// The same as event_to_binding() except that key presses with Ctrl, Shift, Alt or Meta held return None as a Binding can't hold modifiers
+    fn plain_binding(event: &InputEvent) -> Option<Binding> {
+        unsafe {
+            if let Some(modifiers) = event.cast::<InputEventWithModifiers>() {
+                if modifiers.get_control() || modifiers.get_shift() || modifiers.get_alt() || modifiers.get_metakey() {
+                    return None;
+                }
+            }
+        }
+        GodotBackend::event_to_binding(event)
+    }
+
+    fn visual_kind(node: Node) -> Option<VisualKind> {
+        unsafe {
+            if node.cast::<CanvasItem>().is_some() {
+                Some(VisualKind::Canvas)
+            } else if node.cast::<Spatial>().is_some() {
+                Some(VisualKind::Spatial)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Godot only hides a CanvasItem under a hidden CanvasItem and a Spatial under a hidden Spatial,
+    /// so this finds the nodes below a hidden node that godot doesn't hide (such as a Spatial model under a Node2D state) and hides them too.
+    /// Only the nodes that were hidden this way are shown again, nodes that were already hidden stay hidden
+    unsafe fn set_visible_below(node: Node, parent_kind: Option<VisualKind>, visible: bool) {
+        let meta = GodotString::from_str(HIDDEN_META);
+        for i in 0..node.get_child_count() {
+            let mut child = match node.get_child(i) {
+                Some(child) => child,
+                None => continue,
+            };
+
+            let kind = GodotBackend::visual_kind(child);
+            if kind.is_some() && kind != parent_kind {
+                let mut shown = match kind {
+                    Some(VisualKind::Canvas) => child.cast::<CanvasItem>().unwrap().is_visible(),
+                    _ => child.cast::<Spatial>().unwrap().is_visible(),
+                };
+                if !visible && shown {
+                    child.set_meta(meta.clone(), Variant::from_bool(true));
+                    shown = false;
+                } else if visible && child.has_meta(meta.clone()) {
+                    child.remove_meta(meta.clone());
+                    shown = true;
+                }
+                match kind {
+                    Some(VisualKind::Canvas) => child.cast::<CanvasItem>().unwrap().set_visible(shown),
+                    _ => child.cast::<Spatial>().unwrap().set_visible(shown),
+                }
+            }
+            GodotBackend::set_visible_below(child, kind, visible);
+        }
+    }
+
+    /// Turns off processing and stops the animations of the node and all of its children.
+    /// What was running is stored in the node's metadata so that unfreezing only starts back up what was stopped
+    unsafe fn freeze(mut node: Node, frozen: bool) {
+        let meta = GodotString::from_str(FROZEN_META);
+        if frozen && !node.has_meta(meta.clone()) {
+            let mut running = 0;
+            if node.is_processing() { running |= FROZE_PROCESS; }
+            if node.is_physics_processing() { running |= FROZE_PHYSICS_PROCESS; }
+            if node.is_processing_input() { running |= FROZE_INPUT; }
+            if node.is_processing_unhandled_input() { running |= FROZE_UNHANDLED_INPUT; }
+            node.set_process(false);
+            node.set_physics_process(false);
+            node.set_process_input(false);
+            node.set_process_unhandled_input(false);
+
+            if let Some(mut sprite) = node.cast::<AnimatedSprite>() {
+                if sprite.is_playing() {
+                    running |= FROZE_ANIMATION;
+                    sprite.stop();
+                }
+            }
+            if let Some(mut player) = node.cast::<AnimationPlayer>() {
+                if player.is_playing() {
+                    running |= FROZE_ANIMATION;
+                    player.stop(false);
+                }
+            }
+            node.set_meta(meta, Variant::from_i64(running));
+        } else if !frozen && node.has_meta(meta.clone()) {
+            let running = node.get_meta(meta.clone()).to_i64();
+            node.set_process(running & FROZE_PROCESS != 0);
+            node.set_physics_process(running & FROZE_PHYSICS_PROCESS != 0);
+            node.set_process_input(running & FROZE_INPUT != 0);
+            node.set_process_unhandled_input(running & FROZE_UNHANDLED_INPUT != 0);
+
+            if running & FROZE_ANIMATION != 0 {
+                if let Some(mut sprite) = node.cast::<AnimatedSprite>() {
+                    let animation = sprite.get_animation();
+                    sprite.play(animation, false);
+                }
+                if let Some(mut player) = node.cast::<AnimationPlayer>() {
+                    player.play(GodotString::new(), -1f64, 1f64, false);
+                }
+            }
+            node.remove_meta(meta);
+        }
+
+        for i in 0..node.get_child_count() {
+            if let Some(child) = node.get_child(i) {
+                GodotBackend::freeze(child, frozen);
+            }
+        }
+    }
+
+    fn binding_to_event(binding: Binding) -> InputEvent {
+        unsafe {
+            match binding {
+                Binding::Key(scancode) => {
+                    let mut key = InputEventKey::new();
+                    key.set_scancode(scancode);
+                    key.cast::<InputEvent>().unwrap()
+                },
+                Binding::MouseButton(index) => {
+                    let mut button = InputEventMouseButton::new();
+                    button.set_button_index(index);
+                    button.cast::<InputEvent>().unwrap()
+                },
+                Binding::JoyButton(index) => {
+                    let mut button = InputEventJoypadButton::new();
+                    button.set_button_index(index);
+                    button.cast::<InputEvent>().unwrap()
+                },
+                Binding::JoyAxis(axis, positive) => {
+                    let mut motion = InputEventJoypadMotion::new();
+                    motion.set_axis(axis);
+                    motion.set_axis_value(if positive { 1f64 } else { -1f64 });
+                    motion.cast::<InputEvent>().unwrap()
+                },
+            }
+        }
+    }
+}
+
+impl SceneBackend for GodotBackend {
+    fn root(&self) -> SceneNode {
+        SceneNode::Godot(self.owner)
+    }
+
+    fn create_node(&mut self, parent: SceneNode, name: &str, kind: NodeKind) -> SceneNode {
+        unsafe {
+            let mut node = match kind {
+                NodeKind::Node => Node::new(),
+                NodeKind::Node2D => Node2D::new().cast::<Node>().unwrap(),
+                NodeKind::Spatial => Spatial::new().cast::<Node>().unwrap(),
+            };
+            node.set_name(GodotString::from_str(name));
+            GodotBackend::node(parent).add_child(Some(node), true);
+            SceneNode::Godot(node)
+        }
+    }
+
+    fn instance_scene(&mut self, parent: SceneNode, name: &str, scene: Option<&PackedScene>, _model: usize) -> Option<SceneNode> {
+        let scene = scene?;
+        unsafe {
+            let mut instance = scene.instance(0)?.cast::<Node>()?;
+            instance.set_name(GodotString::from_str(name));
+            GodotBackend::node(parent).add_child(Some(instance), true);
+            Some(SceneNode::Godot(instance))
+        }
+    }
+
+    fn start_load(&mut self, path: &str) -> Result<usize, String> {
+        match ResourceLoader::godot_singleton().load_interactive(
+            GodotString::from_str(format!("res://scenes/{}.tscn", path)),
+            GodotString::from_str("PackedScene"),
+        ) {
+            Some(loader) => {
+                self.loaders.push(Some(loader));
+                Ok(self.loaders.len() - 1)
+            },
+            None => Err(format!("Could not find {}", path)),
+        }
+    }
+
+    fn poll_load(&mut self, handle: usize) -> LoadStatus {
+        let mut loader = match self.loaders.get(handle).and_then(|loader| loader.clone()) {
+            Some(loader) => loader,
+            None => return LoadStatus::Failed(format!("Load {} does not exist", handle)),
+        };
+
+        let status = unsafe {
+            match loader.poll() {
+                Ok(()) => {
+                    let stage_count = loader.get_stage_count().max(1);
+                    LoadStatus::Loading(loader.get_stage() as f32 / stage_count as f32)
+                },
+                Err(GodotError::FileEof) => match loader.get_resource().and_then(|resource| resource.cast::<PackedScene>()) {
+                    Some(scene) => LoadStatus::Loaded(Some(scene)),
+                    None => LoadStatus::Failed("Could not cast to PackedScene".into()),
+                },
+                Err(error) => LoadStatus::Failed(format!("{:?}", error)),
+            }
+        };
+
+        // Drop the loader once it has finished
+        match status {
+            LoadStatus::Loading(_) => {},
+            _ => self.loaders[handle] = None,
+        }
+        status
+    }
+
+    fn free_node(&mut self, node: SceneNode) {
+        unsafe { GodotBackend::node(node).free(); }
+    }
+
+    fn get_parent(&self, node: SceneNode) -> Option<SceneNode> {
+        unsafe { GodotBackend::node(node).get_parent().map(SceneNode::Godot) }
+    }
+
+    fn reparent(&mut self, node: SceneNode, new_parent: SceneNode) {
+        let node = GodotBackend::node(node);
+        unsafe {
+            if let Some(mut parent) = node.get_parent() {
+                parent.remove_child(Some(node));
+            }
+            GodotBackend::node(new_parent).add_child(Some(node), true);
+        }
+    }
+
+    fn raise(&mut self, node: SceneNode) {
+        let node = GodotBackend::node(node);
+        unsafe {
+            if let Some(mut parent) = node.get_parent() {
+                let last = parent.get_child_count() - 1;
+                parent.move_child(Some(node), last);
+            }
+        }
+    }
+
+    fn get_child_count(&self, node: SceneNode) -> usize {
+        unsafe { GodotBackend::node(node).get_child_count() as usize }
+    }
+
+    fn set_visible(&mut self, node: SceneNode, visible: bool) {
+        let node = GodotBackend::node(node);
+        unsafe {
+            let kind = GodotBackend::visual_kind(node);
+            match kind {
+                Some(VisualKind::Canvas) => node.cast::<CanvasItem>().unwrap().set_visible(visible),
+                Some(VisualKind::Spatial) => node.cast::<Spatial>().unwrap().set_visible(visible),
+                None => {},
+            }
+            GodotBackend::set_visible_below(node, kind, visible);
+        }
+    }
+
+    fn set_paused(&mut self, node: SceneNode, paused: bool) {
+        unsafe { GodotBackend::freeze(GodotBackend::node(node), paused); }
+    }
+
+    fn set_transform(&mut self, node: SceneNode, transform: &Position) {
+        sync_transform_to_node(transform, GodotBackend::node(node));
+    }
+
+    fn set_transform_3d(&mut self, node: SceneNode, transform: &Position3D) {
+        sync_transform_3d_to_node(transform, GodotBackend::node(node));
+    }
+
+    fn set_anim_sprite(&mut self, node: SceneNode, state: &AnimSprite) {
+        if let Some(mut sprite) = get_animator::<AnimatedSprite>(GodotBackend::node(node)) {
+            // Update node from state
+            unsafe {
+                sprite._set_playing(state.playing);
+                let gd_string = GodotString::from(state.animation);
+                sprite.play(gd_string, false);
+                sprite.set_flip_h(state.flip_h);
+                sprite.set_flip_v(state.flip_v);
+            }
+        }
+    }
+
+    fn poll_input(&mut self, input: &mut InputState) {
+        let godot_input = Input::godot_singleton();
+        let actions = InputMap::godot_singleton().get_actions();
+        for i in 0..actions.len() {
+            let action = actions.get_val(i).to_string();
+            let strength = godot_input.get_action_strength(GodotString::from_str(&action));
+            if godot_input.is_action_pressed(GodotString::from_str(&action)) {
+                input.set_action_strength(&action, strength as f32);
+                input.press(&action);
+            } else {
+                input.release(&action);
+            }
+        }
+
+        let joypads = godot_input.get_connected_joypads();
+        let joypads: Vec<i64> = (0..joypads.len()).map(|i| joypads.get_val(i).to_i64()).collect();
+        for device in joypads.iter() {
+            for axis in 0..JOY_AXIS_COUNT {
+                input.set_joy_axis(*device, axis, godot_input.get_joy_axis(*device, axis) as f32);
+            }
+        }
+        input.set_connected_joypads(joypads);
+    }
+
+    fn input_event(&mut self, event: &InputEvent, input: &mut InputState) {
+        unsafe {
+            if let Some(mouse) = event.cast::<InputEventMouse>() {
+                input.set_mouse_position(mouse.get_position());
+            }
+
+            if let Some(button) = event.cast::<InputEventMouseButton>() {
+                if event.is_pressed() {
+                    input.press_mouse_button(button.get_button_index());
+                } else {
+                    input.release_mouse_button(button.get_button_index());
+                }
+            }
+
+            if let Some(touch) = event.cast::<InputEventScreenTouch>() {
+                if touch.get_index() == 0 {
+                    if touch.is_pressed() {
+                        input.begin_touch(touch.get_position());
+                    } else {
+                        input.end_touch();
+                    }
+                }
+            }
+
+            if let Some(drag) = event.cast::<InputEventScreenDrag>() {
+                if drag.get_index() == 0 {
+                    input.drag_touch(drag.get_position());
+                }
+            }
+
+            if let Some(key) = event.cast::<InputEventKey>() {
+                let unicode = key.get_unicode() as u32;
+                if event.is_pressed() && unicode != 0 {
+                    if let Some(character) = std::char::from_u32(unicode) {
+                        input.push_text(&character.to_string());
+                    }
+                }
+            }
+
+            if event.is_pressed() && !event.is_echo() {
+                if let Some(binding) = GodotBackend::event_to_binding(event) {
+                    input.set_last_pressed(binding);
+                }
+            }
+        }
+    }
+
+    fn read_bindings(&self) -> Vec<(String, Vec<Binding>)> {
+        let input_map = InputMap::godot_singleton();
+        let actions = input_map.get_actions();
+        let mut bindings = vec![];
+        for i in 0..actions.len() {
+            let action = actions.get_val(i).to_string();
+            let events = input_map.get_action_list(GodotString::from_str(&action));
+            let mut action_bindings = vec![];
+            for j in 0..events.len() {
+                if let Some(event) = events.get_val(j).try_to_object::<InputEvent>() {
+                    if let Some(binding) = GodotBackend::plain_binding(&event) {
+                        action_bindings.push(binding);
+                    }
+                }
+            }
+            bindings.push((action, action_bindings));
+        }
+        bindings
+    }
+
+    fn write_bindings(&mut self, bindings: &[(String, Vec<Binding>)]) {
+        let mut input_map = InputMap::godot_singleton();
+        for (action, action_bindings) in bindings.iter() {
+            let action = GodotString::from_str(action);
+            if !input_map.has_action(action.new_ref()) {
+                input_map.add_action(action.new_ref(), 0.5);
+            }
+
+            // Only events that a Binding can represent are touched, key combos with modifiers and other events are left as they are
+            let mut existing = vec![];
+            let events = input_map.get_action_list(action.new_ref());
+            for i in 0..events.len() {
+                if let Some(event) = events.get_val(i).try_to_object::<InputEvent>() {
+                    match GodotBackend::plain_binding(&event) {
+                        Some(binding) if action_bindings.contains(&binding) => existing.push(binding),
+                        Some(_) => input_map.action_erase_event(action.new_ref(), Some(event)),
+                        None => {},
+                    }
+                }
+            }
+
+            for binding in action_bindings.iter().filter(|binding| !existing.contains(binding)) {
+                input_map.action_add_event(action.new_ref(), Some(GodotBackend::binding_to_event(*binding)));
+            }
+        }
+    }
+
+    fn user_dir(&self) -> String {
+        OS::godot_singleton().get_user_data_dir().to_string()
+    }
+
+    fn print(&mut self, message: &str) {
+        godot_print!("{}", message);
+    }
+
+    fn ready(&mut self) {
+        // Window close requests are handled by the engine so that states can intercept them
+        unsafe {
+            if let Some(mut tree) = self.owner.get_tree() {
+                tree.set_auto_accept_quit(false);
+            }
+        }
+    }
+
+    fn quit(&mut self) {
+        unsafe {
+            if let Some(mut tree) = self.owner.get_tree() {
+                tree.quit(-1);
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A record of a node created by the HeadlessBackend
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeadlessNode {
+    pub name: String,
+    pub kind: NodeKind,
+    /// The index of the model this node was instanced from
+    pub model: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub transform: Option<Position>,
+    pub transform_3d: Option<Position3D>,
+    pub visible: bool,
+    /// Set when the node was hidden because a node of the other visual kind above it was hidden
+    pub hidden_by_parent: bool,
+    pub paused: bool,
+    pub animation: Option<AnimSprite>,
+}
+
+impl HeadlessNode {
+    fn new(name: &str, kind: NodeKind, parent: Option<usize>) -> Self {
+        HeadlessNode {
+            name: name.into(),
+            kind,
+            model: None,
+            parent,
+            children: vec![],
+            transform: None,
+            transform_3d: None,
+            visible: true,
+            hidden_by_parent: false,
+            paused: false,
+            animation: None,
+        }
+    }
+}
+
+/// A SceneBackend that records a fake node tree in memory instead of talking to godot
+///
+/// Freed nodes are removed from the tree but their index is never reused,
+/// so a stale SceneNode will return None rather than pointing at a different node.
+pub struct HeadlessBackend {
+    nodes: Vec<Option<HeadlessNode>>,
+    loads: Vec<String>,
+    missing: Vec<String>,
+    held: Vec<String>,
+    log: Vec<String>,
+    quit: bool,
+    bindings: Vec<(String, Vec<Binding>)>,
+}
+
+impl Default for HeadlessBackend {
+    fn default() -> Self {
+        HeadlessBackend {
+            nodes: vec![Some(HeadlessNode::new("Root", NodeKind::Node, None))],
+            loads: vec![],
+            missing: vec![],
+            held: vec![],
+            log: vec![],
+            quit: false,
+            bindings: vec![],
+        }
+    }
+}
+
+impl HeadlessBackend {
+    pub fn new() -> Self {
+        HeadlessBackend::default()
+    }
+
+    /// The index of the root node that the engine adds state nodes to
+    pub fn root_index(&self) -> usize {
+        0
+    }
+
+    pub fn get(&self, index: usize) -> Option<&HeadlessNode> {
+        self.nodes.get(index).and_then(|node| node.as_ref())
+    }
+
+    pub fn get_node(&self, node: SceneNode) -> Option<&HeadlessNode> {
+        self.get(node.headless()?)
+    }
+
+    /// Finds a node from a path of names separated by / relative to the root node
+    /// e.g. "Layer: game/State0: Base/RenderablesContainer"
+    pub fn find(&self, path: &str) -> Option<usize> {
+        let mut current = self.root_index();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            current = *self.get(current)?.children.iter()
+                .find(|child| self.get(**child).map_or(false, |child| child.name == name))?;
+        }
+        Some(current)
+    }
+
+    /// Returns true if the node and the parents it inherits visibility from are visible.
+    /// This follows godot, a Node2D only inherits from Node2D parents and a Spatial only from Spatial parents
+    /// so a node below a parent of another kind is treated as top level. Plain nodes pass on their parent's visibility
+    pub fn is_visible_in_tree(&self, index: usize) -> bool {
+        let node = match self.get(index) {
+            Some(node) => node,
+            None => return false,
+        };
+        if !node.visible {
+            return false;
+        }
+        match node.parent.and_then(|parent| self.get(parent).map(|parent_node| (parent, parent_node.kind))) {
+            Some((parent, kind)) if node.kind == NodeKind::Node || kind == node.kind => self.is_visible_in_tree(parent),
+            _ => true,
+        }
+    }
+
+    /// Returns true if the node or any of its parents are paused
+    pub fn is_paused_in_tree(&self, index: usize) -> bool {
+        match self.get(index) {
+            Some(node) => node.paused || node.parent.map_or(false, |parent| self.is_paused_in_tree(parent)),
+            None => false,
+        }
+    }
+
+    /// Returns the names of all the children of a node in the order they were added
+    pub fn child_names(&self, index: usize) -> Vec<String> {
+        match self.get(index) {
+            Some(node) => node.children.iter()
+                .filter_map(|child| self.get(*child))
+                .map(|child| child.name.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// The number of nodes currently in the tree including the root node
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().filter(|node| node.is_some()).count()
+    }
+
+    /// Makes any background load of path fail, by default every load succeeds on its first poll
+    pub fn set_missing(&mut self, path: &str) {
+        self.missing.push(path.into());
+    }
+
+    /// Keeps any background load of path at half way until release_load() is called
+    pub fn hold_load(&mut self, path: &str) {
+        self.held.push(path.into());
+    }
+
+    pub fn release_load(&mut self, path: &str) {
+        self.held.retain(|held| held != path);
+    }
+
+    /// Adds an input action the same way project.godot's [input] section does, call VermarineEngine::reload_bindings() afterwards
+    pub fn add_action(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.retain(|(name, _)| name != action);
+        self.bindings.push((action.into(), bindings));
+    }
+
+    /// Returns true once the engine has finished quitting
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Every message that has been passed to print()
+    pub fn log(&self) -> &Vec<String> {
+        &self.log
+    }
+
+    fn add(&mut self, parent: SceneNode, node: HeadlessNode) -> SceneNode {
+        let index = self.nodes.len();
+        let parent = HeadlessBackend::index(parent);
+        let mut node = node;
+        node.parent = Some(parent);
+        self.nodes.push(Some(node));
+        if let Some(Some(parent)) = self.nodes.get_mut(parent) {
+            parent.children.push(index);
+        }
+        SceneNode::Headless(index)
+    }
+
+    fn index(node: SceneNode) -> usize {
+        match node.headless() {
+            Some(index) => index,
+            None => panic!("Attempt to use {:?} with the HeadlessBackend failed", node),
+        }
+    }
+
+    fn get_mut(&mut self, node: SceneNode) -> Option<&mut HeadlessNode> {
+        self.nodes.get_mut(HeadlessBackend::index(node)).and_then(|node| node.as_mut())
+    }
+
+    /// The same as GodotBackend::set_visible_below, nodes of the other visual kind below a hidden node are hidden with it
+    /// and only the nodes hidden this way are shown again
+    fn set_visible_below(&mut self, index: usize, parent_kind: NodeKind, visible: bool) {
+        let children = self.get(index).map_or(vec![], |node| node.children.clone());
+        for child in children {
+            let kind = match self.nodes.get_mut(child).and_then(|node| node.as_mut()) {
+                Some(node) => {
+                    if node.kind != NodeKind::Node && node.kind != parent_kind {
+                        if !visible && node.visible {
+                            node.hidden_by_parent = true;
+                            node.visible = false;
+                        } else if visible && node.hidden_by_parent {
+                            node.hidden_by_parent = false;
+                            node.visible = true;
+                        }
+                    }
+                    node.kind
+                },
+                None => continue,
+            };
+            self.set_visible_below(child, kind, visible);
+        }
+    }
+}
+
+impl SceneBackend for HeadlessBackend {
+    fn root(&self) -> SceneNode {
+        SceneNode::Headless(self.root_index())
+    }
+
+    fn create_node(&mut self, parent: SceneNode, name: &str, kind: NodeKind) -> SceneNode {
+        self.add(parent, HeadlessNode::new(name, kind, None))
+    }
+
+    fn instance_scene(&mut self, parent: SceneNode, name: &str, _scene: Option<&PackedScene>, model: usize) -> Option<SceneNode> {
+        // Models are scenes of the same kind as the container they are placed in
+        let kind = match self.get_node(parent).map(|parent| parent.kind) {
+            Some(NodeKind::Spatial) => NodeKind::Spatial,
+            _ => NodeKind::Node2D,
+        };
+        let mut node = HeadlessNode::new(name, kind, None);
+        node.model = Some(model);
+        Some(self.add(parent, node))
+    }
+
+    fn start_load(&mut self, path: &str) -> Result<usize, String> {
+        if self.missing.iter().any(|missing| missing == path) {
+            return Err(format!("Could not find {}", path));
+        }
+        self.loads.push(path.into());
+        Ok(self.loads.len() - 1)
+    }
+
+    fn poll_load(&mut self, handle: usize) -> LoadStatus {
+        match self.loads.get(handle) {
+            Some(path) if self.held.contains(path) => LoadStatus::Loading(0.5f32),
+            Some(_) => LoadStatus::Loaded(None),
+            None => LoadStatus::Failed(format!("Load {} does not exist", handle)),
+        }
+    }
+
+    fn free_node(&mut self, node: SceneNode) {
+        let index = HeadlessBackend::index(node);
+        let removed = match self.nodes.get_mut(index) {
+            Some(node) => node.take(),
+            None => None,
+        };
+
+        if let Some(removed) = removed {
+            if let Some(Some(parent)) = removed.parent.and_then(|parent| self.nodes.get_mut(parent)) {
+                parent.children.retain(|child| *child != index);
+            }
+            for child in removed.children {
+                self.free_node(SceneNode::Headless(child));
+            }
+        }
+    }
+
+    fn get_parent(&self, node: SceneNode) -> Option<SceneNode> {
+        self.get_node(node)?.parent.map(SceneNode::Headless)
+    }
+
+    fn reparent(&mut self, node: SceneNode, new_parent: SceneNode) {
+        let index = HeadlessBackend::index(node);
+        let new_index = HeadlessBackend::index(new_parent);
+
+        let old_parent = match self.get_mut(node) {
+            Some(node) => node.parent.replace(new_index),
+            None => return,
+        };
+        if let Some(Some(parent)) = old_parent.and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.children.retain(|child| *child != index);
+        }
+        if let Some(parent) = self.get_mut(new_parent) {
+            parent.children.push(index);
+        }
+    }
+
+    fn raise(&mut self, node: SceneNode) {
+        let index = HeadlessBackend::index(node);
+        let parent = match self.get_node(node) {
+            Some(node) => node.parent,
+            None => return,
+        };
+        if let Some(Some(parent)) = parent.and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.children.retain(|child| *child != index);
+            parent.children.push(index);
+        }
+    }
+
+    fn get_child_count(&self, node: SceneNode) -> usize {
+        self.get_node(node).map_or(0, |node| node.children.len())
+    }
+
+    fn set_visible(&mut self, node: SceneNode, visible: bool) {
+        let kind = match self.get_mut(node) {
+            Some(headless) => {
+                headless.visible = visible;
+                headless.kind
+            },
+            None => return,
+        };
+        self.set_visible_below(HeadlessBackend::index(node), kind, visible);
+    }
+
+    fn set_paused(&mut self, node: SceneNode, paused: bool) {
+        if let Some(node) = self.get_mut(node) {
+            node.paused = paused;
+        }
+    }
+
+    fn set_transform(&mut self, node: SceneNode, transform: &Position) {
+        if let Some(node) = self.get_mut(node) {
+            node.transform = Some(*transform);
+        }
+    }
+
+    fn set_transform_3d(&mut self, node: SceneNode, transform: &Position3D) {
+        if let Some(node) = self.get_mut(node) {
+            node.transform_3d = Some(*transform);
+        }
+    }
+
+    fn set_anim_sprite(&mut self, node: SceneNode, state: &AnimSprite) {
+        if let Some(node) = self.get_mut(node) {
+            node.animation = Some(*state);
+        }
+    }
+
+    fn poll_input(&mut self, _input: &mut InputState) {
+        // Input is injected directly into the InputState resource when running headless
+    }
+
+    fn input_event(&mut self, _event: &InputEvent, _input: &mut InputState) {
+    }
+
+    fn read_bindings(&self) -> Vec<(String, Vec<Binding>)> {
+        self.bindings.clone()
+    }
+
+    fn write_bindings(&mut self, bindings: &[(String, Vec<Binding>)]) {
+        for (action, action_bindings) in bindings.iter() {
+            self.add_action(action, action_bindings.clone());
+        }
+    }
+
+    fn user_dir(&self) -> String {
+        std::env::temp_dir().join("vermarine").to_string_lossy().into_owned()
+    }
+
+    fn print(&mut self, message: &str) {
+        self.log.push(message.into());
+    }
+
+    fn ready(&mut self) {
+    }
+
+    fn quit(&mut self) {
+        self.quit = true;
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
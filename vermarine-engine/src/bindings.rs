@@ -0,0 +1,355 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+/// A physical input that an action can be bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// A keyboard key by godot scancode
+    Key(i64),
+    /// A mouse button by godot button index
+    MouseButton(i64),
+    /// A joypad button by godot button index
+    JoyButton(i64),
+    /// A joypad axis by godot axis index, true if the action is pressed by moving the axis in the positive direction
+    JoyAxis(i64, bool),
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Binding::Key(scancode) => write!(f, "key:{}", scancode),
+            Binding::MouseButton(button) => write!(f, "mouse:{}", button),
+            Binding::JoyButton(button) => write!(f, "joy_button:{}", button),
+            Binding::JoyAxis(axis, positive) => write!(f, "joy_axis:{}:{}", axis, if *positive { "+" } else { "-" }),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let index = |i: usize| -> Result<i64, String> {
+            parts.get(i).and_then(|part| part.parse().ok()).ok_or_else(|| format!("{} is not a valid binding", s))
+        };
+
+        match parts[0] {
+            "key" => Ok(Binding::Key(index(1)?)),
+            "mouse" => Ok(Binding::MouseButton(index(1)?)),
+            "joy_button" => Ok(Binding::JoyButton(index(1)?)),
+            "joy_axis" => match parts.get(2) {
+                Some(&"+") => Ok(Binding::JoyAxis(index(1)?, true)),
+                Some(&"-") => Ok(Binding::JoyAxis(index(1)?, false)),
+                _ => Err(format!("{} is not a valid binding", s)),
+            },
+            _ => Err(format!("{} is not a valid binding", s)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BindError {
+    /// There is no action with this name in the InputMap
+    UnknownAction(String),
+    /// The binding is already used by these actions
+    Conflict(Vec<String>),
+}
+
+/// The InputBindings resource holds every action in godot's InputMap and what it is bound to.
+///
+/// Changes made to it are written back to the InputMap by the engine at the end of the frame, so a controls menu can be an ordinary state.
+/// Only the actions that changed are written, and key combos with modifiers such as Ctrl+S are left in the InputMap untouched as a Binding can't represent them.
+/// Paths passed to save() and load() can start with user:// the same way they can in godot.
+///
+/// Example of rebinding an action to whatever the player presses next:
+/// ```
+/// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     let pressed = resources.get::<InputState>().unwrap().last_pressed();
+///     if let Some(binding) = pressed {
+///         let mut bindings = resources.get_mut::<InputBindings>().unwrap();
+///         match bindings.bind(&self.action, binding) {
+///             Ok(()) => bindings.save("user://controls.cfg").unwrap(),
+///             Err(BindError::Conflict(actions)) => self.conflicts = actions,
+///             Err(_) => {},
+///         }
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct InputBindings {
+    actions: Vec<(String, Vec<Binding>)>,
+    defaults: Vec<(String, Vec<Binding>)>,
+    user_dir: String,
+    changed: Vec<String>,
+}
+
+impl InputBindings {
+    pub(crate) fn new(actions: Vec<(String, Vec<Binding>)>, user_dir: String) -> Self {
+        InputBindings {
+            defaults: actions.clone(),
+            actions,
+            user_dir,
+            changed: vec![],
+        }
+    }
+
+    /// The name of every action
+    pub fn actions(&self) -> Vec<&str> {
+        self.actions.iter().map(|(action, _)| action.as_str()).collect()
+    }
+
+    /// Every binding of the action, this is empty if the action doesn't exist
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        match self.actions.iter().find(|(name, _)| name == action) {
+            Some((_, bindings)) => &bindings[..],
+            None => &[],
+        }
+    }
+
+    /// The actions that are bound to the binding
+    pub fn conflicts(&self, binding: Binding) -> Vec<String> {
+        self.actions.iter()
+            .filter(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| action.clone())
+            .collect()
+    }
+
+    /// Adds a binding to the action
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the action doesn't exist or the binding is already used by another action
+    pub fn bind(&mut self, action: &str, binding: Binding) -> Result<(), BindError> {
+        let conflicts: Vec<String> = self.conflicts(binding).into_iter().filter(|conflict| conflict != action).collect();
+        if !conflicts.is_empty() {
+            return Err(BindError::Conflict(conflicts));
+        }
+        self.bind_replacing(action, binding)
+    }
+
+    /// Adds a binding to the action and removes it from any other actions that use it
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the action doesn't exist
+    pub fn bind_replacing(&mut self, action: &str, binding: Binding) -> Result<(), BindError> {
+        if !self.actions.iter().any(|(name, _)| name == action) {
+            return Err(BindError::UnknownAction(action.into()));
+        }
+
+        let mut changed = vec![];
+        for (name, bindings) in self.actions.iter_mut() {
+            if name == action {
+                if !bindings.contains(&binding) {
+                    bindings.push(binding);
+                    changed.push(name.clone());
+                }
+            } else if bindings.contains(&binding) {
+                bindings.retain(|other| *other != binding);
+                changed.push(name.clone());
+            }
+        }
+        for name in changed {
+            self.mark_changed(&name);
+        }
+        Ok(())
+    }
+
+    /// Removes every binding of the action and binds it to the binding, the binding is removed from any other actions that use it
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the action doesn't exist
+    pub fn rebind(&mut self, action: &str, binding: Binding) -> Result<(), BindError> {
+        self.clear(action);
+        self.bind_replacing(action, binding)
+    }
+
+    /// Removes the binding from the action, nothing happens if the action doesn't have it
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some((_, bindings)) = self.actions.iter_mut().find(|(name, _)| name == action) {
+            let count = bindings.len();
+            bindings.retain(|other| *other != binding);
+            if bindings.len() != count {
+                self.mark_changed(action);
+            }
+        }
+    }
+
+    /// Removes every binding of the action
+    pub fn clear(&mut self, action: &str) {
+        if let Some((_, bindings)) = self.actions.iter_mut().find(|(name, _)| name == action) {
+            if !bindings.is_empty() {
+                bindings.clear();
+                self.mark_changed(action);
+            }
+        }
+    }
+
+    /// Sets every action back to the bindings from project.godot
+    pub fn reset_to_defaults(&mut self) {
+        self.actions = self.defaults.clone();
+        self.changed = self.actions.iter().map(|(action, _)| action.clone()).collect();
+    }
+
+    /// Saves the bindings as text with one action per line
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the file could not be written to
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let path = self.resolve(path);
+        if let Some(parent) = std::path::Path::new(&path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_text())
+    }
+
+    /// Loads bindings saved with save(), actions in the file that don't exist anymore are skipped and actions missing from the file are left as they are
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the file could not be read or a binding in it is invalid
+    pub fn load(&mut self, path: &str) -> std::io::Result<()> {
+        let text = std::fs::read_to_string(self.resolve(path))?;
+        for line in text.lines() {
+            let mut values = line.split_whitespace();
+            let action = match values.next() {
+                Some(action) => action,
+                None => continue,
+            };
+
+            let mut bindings = vec![];
+            for value in values {
+                match value.parse::<Binding>() {
+                    Ok(binding) => bindings.push(binding),
+                    Err(message) => return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message)),
+                }
+            }
+
+            if let Some((_, existing)) = self.actions.iter_mut().find(|(name, _)| name == action) {
+                *existing = bindings;
+                self.mark_changed(action);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut output = String::new();
+        for (action, bindings) in self.actions.iter() {
+            output.push_str(action);
+            for binding in bindings.iter() {
+                output.push_str(&format!(" {}", binding));
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Swaps user:// for the backend's user directory
+    fn resolve(&self, path: &str) -> String {
+        const USER_PREFIX: &str = "user://";
+        if path.starts_with(USER_PREFIX) {
+            format!("{}/{}", self.user_dir.trim_end_matches('/'), &path[USER_PREFIX.len()..])
+        } else {
+            path.into()
+        }
+    }
+
+    fn mark_changed(&mut self, action: &str) {
+        if !self.changed.iter().any(|changed| changed == action) {
+            self.changed.push(action.into());
+        }
+    }
+
+    /// Returns the actions that have changed since this was last called along with their bindings, these are what the engine writes back to the backend
+    pub(crate) fn take_changed(&mut self) -> Vec<(String, Vec<Binding>)> {
+        let changed = std::mem::replace(&mut self.changed, vec![]);
+        self.actions.iter()
+            .filter(|(action, _)| changed.contains(action))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    fn bindings() -> InputBindings {
+        InputBindings::new(vec![
+            ("jump".into(), vec![Binding::Key(32)]),
+            ("fire".into(), vec![Binding::MouseButton(1), Binding::JoyAxis(5, true)]),
+        ], std::env::temp_dir().to_string_lossy().into())
+    }
+
+    #[test]
+    fn bindings_round_trip_through_text() {
+        for binding in [Binding::Key(65), Binding::MouseButton(2), Binding::JoyButton(0), Binding::JoyAxis(1, false)].iter() {
+            assert_eq!(binding.to_string().parse::<Binding>(), Ok(*binding));
+        }
+        assert!("key:".parse::<Binding>().is_err());
+        assert!("joy_axis:1:?".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn saved_bindings_load_back() {
+        let name = format!("vermarine-{}-saved_bindings_load_back.cfg", std::process::id());
+        let path = format!("user://{}", name);
+        let mut saved = bindings();
+        saved.rebind("jump", Binding::JoyButton(0)).unwrap();
+        saved.save(&path).unwrap();
+
+        let mut loaded = bindings();
+        let result = loaded.load(&path);
+        std::fs::remove_file(std::env::temp_dir().join(name)).unwrap();
+        result.unwrap();
+        assert_eq!(loaded.to_text(), saved.to_text());
+        assert_eq!(loaded.bindings("jump"), &[Binding::JoyButton(0)]);
+    }
+
+    #[test]
+    fn only_removing_a_binding_marks_the_action_changed() {
+        let mut bindings = bindings();
+        bindings.unbind("jump", Binding::Key(65));
+        assert!(bindings.take_changed().is_empty());
+
+        bindings.unbind("jump", Binding::Key(32));
+        assert_eq!(bindings.take_changed(), vec![("jump".to_string(), vec![])]);
+
+        bindings.clear("jump");
+        assert!(bindings.take_changed().is_empty());
+        bindings.clear("fire");
+        assert_eq!(bindings.take_changed().len(), 1);
+    }
+
+    #[test]
+    fn bind_reports_conflicts_and_bind_replacing_moves_the_binding() {
+        let mut bindings = bindings();
+        assert_eq!(bindings.bind("jump", Binding::MouseButton(1)), Err(BindError::Conflict(vec!["fire".into()])));
+        assert_eq!(bindings.bind("dash", Binding::Key(16)), Err(BindError::UnknownAction("dash".into())));
+        assert!(bindings.take_changed().is_empty());
+
+        bindings.bind_replacing("jump", Binding::MouseButton(1)).unwrap();
+        assert_eq!(bindings.bindings("fire"), &[Binding::JoyAxis(5, true)]);
+        assert_eq!(bindings.take_changed().len(), 2);
+        assert!(bindings.take_changed().is_empty());
+    }
+
+    #[test]
+    fn changed_bindings_are_written_back_to_the_backend() {
+        let mut harness = TestHarness::<i32>::new(Models::default());
+        harness.tree_mut().add_action("jump", vec![Binding::Key(32)]);
+        harness.tree_mut().add_action("fire", vec![Binding::MouseButton(1)]);
+        harness.engine_mut().reload_bindings();
+
+        harness.resources().get_mut::<InputBindings>().unwrap().rebind("jump", Binding::Key(87)).unwrap();
+        harness.step();
+        let written = harness.tree().read_bindings();
+        assert!(written.contains(&("jump".into(), vec![Binding::Key(87)])));
+        assert!(written.contains(&("fire".into(), vec![Binding::MouseButton(1)])));
+    }
+}
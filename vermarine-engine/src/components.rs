@@ -1,11 +1,29 @@
 use crate::prelude::*;
 use std::collections::HashMap;
 
+/// The 2D transform of an entity or of a Renderable relative to its parent, this is synced to the Node2D of the entity's container or of the renderable.
+/// ```
+/// let transform = Position::new(100f32, 50f32)
+///     .with_pivot(16f32, 16f32)
+///     .with_rotation(euclid::Angle::degrees(45f32))
+///     .with_scale(2f32, -1f32)
+///     .with_z_index(3);
+/// ```
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
     pub rotation: euclid::Angle::<f32>,
+    /// A negative scale flips the node and its children along that axis
+    pub scale: Vector2,
+    /// How far the y axis is slanted from being perpendicular to the x axis
+    pub skew: euclid::Angle::<f32>,
+    /// The local point that the rotation, scale and skew happen around, x and y are where this point ends up
+    pub pivot: Vector2,
+    /// Nodes with a higher z_index are drawn on top
+    pub z_index: i64,
+    /// If true the z_index is added to the z_index of the parent node
+    pub z_as_relative: bool,
 }
 
 impl Default for Position {
@@ -14,6 +32,11 @@ impl Default for Position {
             x: 0f32,
             y: 0f32,
             rotation: euclid::Angle::radians(0f32),
+            scale: Vector2::new(1f32, 1f32),
+            skew: euclid::Angle::radians(0f32),
+            pivot: Vector2::new(0f32, 0f32),
+            z_index: 0,
+            z_as_relative: true,
         }
     }
 }
@@ -23,32 +46,340 @@ impl Position {
         Position {
             x: x,
             y: y,
-            rotation: euclid::Angle::radians(0f32),
+            ..Position::default()
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: euclid::Angle::<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32) -> Self {
+        self.scale = Vector2::new(x, y);
+        self
+    }
+
+    pub fn with_skew(mut self, skew: euclid::Angle::<f32>) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// Rotates, scales and skews around the local point instead of around 0, 0
+    pub fn with_pivot(mut self, x: f32, y: f32) -> Self {
+        self.pivot = Vector2::new(x, y);
+        self
+    }
+
+    pub fn with_z_index(mut self, z_index: i64) -> Self {
+        self.z_index = z_index;
+        self
+    }
+
+    pub fn with_z_as_relative(mut self, z_as_relative: bool) -> Self {
+        self.z_as_relative = z_as_relative;
+        self
+    }
+
+    /// The translation, rotation, scale, skew and pivot as a godot Transform2D, the z_index is not part of it
+    pub fn to_transform2d(&self) -> Transform2D {
+        let rotation = self.rotation.get();
+        let skewed = rotation + self.skew.get();
+        let x_axis = Vector2::new(rotation.cos() * self.scale.x, rotation.sin() * self.scale.x);
+        let y_axis = Vector2::new(-skewed.sin() * self.scale.y, skewed.cos() * self.scale.y);
+
+        // Move the origin so that the pivot stays at x, y
+        let pivot = x_axis * self.pivot.x + y_axis * self.pivot.y;
+        Transform2D::row_major(
+            x_axis.x, x_axis.y,
+            y_axis.x, y_axis.y,
+            self.x - pivot.x, self.y - pivot.y,
+        )
+    }
+
+    /// Splits a godot Transform2D back into a translation, rotation, scale and skew with no pivot and a z_index of 0.
+    /// A flipped scale.y comes back as a positive scale with the skew turned by half a circle, which is the same transform
+    pub fn from_transform2d(transform: &Transform2D) -> Self {
+        let rotation = transform.m12.atan2(transform.m11);
+        let skewed = (-transform.m21).atan2(transform.m22);
+        Position {
+            x: transform.m31,
+            y: transform.m32,
+            rotation: euclid::Angle::radians(rotation),
+            scale: Vector2::new(transform.m11.hypot(transform.m12), transform.m21.hypot(transform.m22)),
+            skew: euclid::Angle::radians(skewed - rotation),
+            ..Position::default()
+        }
+    }
+}
+
+/// A rotation in 3D, either as euler angles in radians applied in godot's YXZ order or as a quaternion
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpatialRotation {
+    Euler(Vector3),
+    Quat(Quat),
+}
+
+impl Default for SpatialRotation {
+    fn default() -> Self {
+        SpatialRotation::Euler(Vector3::new(0f32, 0f32, 0f32))
+    }
+}
+
+impl SpatialRotation {
+    /// The rotation as a row major 3x3 matrix
+    fn to_matrix(&self) -> [[f32; 3]; 3] {
+        match self {
+            SpatialRotation::Euler(euler) => {
+                let (sx, cx) = euler.x.sin_cos();
+                let (sy, cy) = euler.y.sin_cos();
+                let (sz, cz) = euler.z.sin_cos();
+                let x = [[1f32, 0f32, 0f32], [0f32, cx, -sx], [0f32, sx, cx]];
+                let y = [[cy, 0f32, sy], [0f32, 1f32, 0f32], [-sy, 0f32, cy]];
+                let z = [[cz, -sz, 0f32], [sz, cz, 0f32], [0f32, 0f32, 1f32]];
+                mul_matrix(&mul_matrix(&y, &x), &z)
+            },
+            SpatialRotation::Quat(quat) => {
+                let (i, j, k, r) = (quat.i, quat.j, quat.k, quat.r);
+                [
+                    [1f32 - 2f32 * (j * j + k * k), 2f32 * (i * j - k * r), 2f32 * (i * k + j * r)],
+                    [2f32 * (i * j + k * r), 1f32 - 2f32 * (i * i + k * k), 2f32 * (j * k - i * r)],
+                    [2f32 * (i * k - j * r), 2f32 * (j * k + i * r), 1f32 - 2f32 * (i * i + j * j)],
+                ]
+            },
+        }
+    }
+}
+
+fn mul_matrix(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut res = [[0f32; 3]; 3];
+    for row in 0..3 {
+        for col in 0..3 {
+            res[row][col] = (0..3).map(|i| a[row][i] * b[i][col]).sum();
         }
     }
+    res
+}
+
+/// The 3D transform of an entity or of a Renderable relative to its parent, this is synced to the Spatial of the entity's container or of the renderable.
+///
+/// Containers are only Spatial nodes when the Renderable has a transform_3d or its model's root node is a Spatial,
+/// so an entity with a Position3D should be given a Renderable made with Renderable::new_3d()
+/// ```
+/// commands.insert((), vec![(
+///     Renderable::new_3d(Position3D::default(), tank.1, tank.0),
+///     Position3D::new(0f32, 2f32, -10f32).with_euler(Vector3::new(0f32, std::f32::consts::PI, 0f32)),
+/// )]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position3D {
+    pub translation: Vector3,
+    pub rotation: SpatialRotation,
+    pub scale: Vector3,
+}
+
+impl Default for Position3D {
+    fn default() -> Self {
+        Position3D {
+            translation: Vector3::new(0f32, 0f32, 0f32),
+            rotation: SpatialRotation::default(),
+            scale: Vector3::new(1f32, 1f32, 1f32),
+        }
+    }
+}
+
+impl Position3D {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Position3D {
+            translation: Vector3::new(x, y, z),
+            ..Position3D::default()
+        }
+    }
+
+    pub fn with_euler(mut self, euler: Vector3) -> Self {
+        self.rotation = SpatialRotation::Euler(euler);
+        self
+    }
+
+    pub fn with_quat(mut self, quat: Quat) -> Self {
+        self.rotation = SpatialRotation::Quat(quat);
+        self
+    }
+
+    pub fn with_scale(mut self, x: f32, y: f32, z: f32) -> Self {
+        self.scale = Vector3::new(x, y, z);
+        self
+    }
+
+    /// The translation, rotation and scale as a godot Transform
+    pub fn to_transform(&self) -> Transform {
+        let rotation = self.rotation.to_matrix();
+        let scale = [self.scale.x, self.scale.y, self.scale.z];
+        let row = |i: usize| Vector3::new(rotation[i][0] * scale[0], rotation[i][1] * scale[1], rotation[i][2] * scale[2]);
+        Transform {
+            basis: Basis { elements: [row(0), row(1), row(2)] },
+            origin: self.translation,
+        }
+    }
+
+    /// Splits a godot Transform back into a translation, rotation and scale, the rotation comes back as a quaternion.
+    /// Transforms that are skewed or mirrored can't be represented so the closest rotation is used
+    pub fn from_transform(transform: &Transform) -> Self {
+        let rows: Vec<[f32; 3]> = transform.basis.elements.iter().map(|row| [row.x, row.y, row.z]).collect();
+        let column = |i: usize| Vector3::new(rows[0][i], rows[1][i], rows[2][i]);
+        let scale = Vector3::new(column(0).length(), column(1).length(), column(2).length());
+        let unscale = |length: f32| if length == 0f32 { 0f32 } else { 1f32 / length };
+        let scales = [unscale(scale.x), unscale(scale.y), unscale(scale.z)];
+        let m = |row: usize, col: usize| rows[row][col] * scales[col];
+
+        // Converts the rotation matrix to a quaternion using whichever diagonal term is largest to stay accurate
+        let trace = m(0, 0) + m(1, 1) + m(2, 2);
+        let (i, j, k, r) = if trace > 0f32 {
+            let s = (trace + 1f32).sqrt() * 2f32;
+            ((m(2, 1) - m(1, 2)) / s, (m(0, 2) - m(2, 0)) / s, (m(1, 0) - m(0, 1)) / s, s / 4f32)
+        } else if m(0, 0) > m(1, 1) && m(0, 0) > m(2, 2) {
+            let s = (1f32 + m(0, 0) - m(1, 1) - m(2, 2)).sqrt() * 2f32;
+            (s / 4f32, (m(0, 1) + m(1, 0)) / s, (m(0, 2) + m(2, 0)) / s, (m(2, 1) - m(1, 2)) / s)
+        } else if m(1, 1) > m(2, 2) {
+            let s = (1f32 + m(1, 1) - m(0, 0) - m(2, 2)).sqrt() * 2f32;
+            ((m(0, 1) + m(1, 0)) / s, s / 4f32, (m(1, 2) + m(2, 1)) / s, (m(0, 2) - m(2, 0)) / s)
+        } else {
+            let s = (1f32 + m(2, 2) - m(0, 0) - m(1, 1)).sqrt() * 2f32;
+            ((m(0, 2) + m(2, 0)) / s, (m(1, 2) + m(2, 1)) / s, s / 4f32, (m(1, 0) - m(0, 1)) / s)
+        };
+
+        Position3D {
+            translation: transform.origin,
+            rotation: SpatialRotation::Quat(Quat::quaternion(i, j, k, r)),
+            scale,
+        }
+    }
+}
+
+impl From<Position> for Position3D {
+    /// Lays a 2D position flat on the ground, y becomes z and the rotation is around the y axis
+    fn from(pos: Position) -> Self {
+        Position3D {
+            translation: Vector3::new(pos.x, 0f32, pos.y),
+            rotation: SpatialRotation::Euler(Vector3::new(0f32, pos.rotation.get(), 0f32)),
+            scale: Vector3::new(pos.scale.x, 1f32, pos.scale.y),
+        }
+    }
+}
+
+/// A stable name for a child of a Renderable, unlike an index it doesn't change when other children are removed.
+///
+/// Strings and numbers can be used as keys directly, an enum can be used by converting it into a ChildKey::Id
+/// ```
+/// #[derive(Clone, Copy)]
+/// pub enum TankPart {
+///     Turret,
+///     Tracks,
+/// }
+///
+/// impl From<TankPart> for ChildKey {
+///     fn from(part: TankPart) -> Self {
+///         ChildKey::Id(part as u64)
+///     }
+/// }
+///
+/// let tank = Renderable::new(Position::default(), body.1, body.0)
+///     .with_child(TankPart::Turret, Renderable::new(Position::new(0f32, -8f32), turret.1, turret.0)
+///         .with_child("barrel", Renderable::new(Position::new(0f32, -16f32), barrel.1, barrel.0)))
+///     .with_child(TankPart::Tracks, Renderable::new(Position::default(), tracks.1, tracks.0));
+///
+/// // --snip
+///
+/// renderable.child_mut(TankPart::Turret).child_mut("barrel").transform.rotation = aim;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ChildKey {
+    Name(String),
+    Id(u64),
+    /// Given to children added with push_child() or insert_child()
+    Unnamed(u64),
+}
+
+impl From<&str> for ChildKey {
+    fn from(name: &str) -> Self {
+        ChildKey::Name(name.into())
+    }
+}
+
+impl From<String> for ChildKey {
+    fn from(name: String) -> Self {
+        ChildKey::Name(name)
+    }
+}
+
+impl From<u64> for ChildKey {
+    fn from(id: u64) -> Self {
+        ChildKey::Id(id)
+    }
+}
+
+impl From<u32> for ChildKey {
+    fn from(id: u32) -> Self {
+        ChildKey::Id(id as u64)
+    }
+}
+
+impl From<usize> for ChildKey {
+    fn from(id: usize) -> Self {
+        ChildKey::Id(id as u64)
+    }
+}
+
+/// Negative numbers wrap around to large ids the same way as i64 does, so -1i32, -1i64 and u64::MAX are all the same key
+impl From<i32> for ChildKey {
+    fn from(id: i32) -> Self {
+        ChildKey::Id(id as u64)
+    }
+}
+
+impl From<i64> for ChildKey {
+    fn from(id: i64) -> Self {
+        ChildKey::Id(id as u64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChildError {
+    /// There is no child with this key
+    MissingKey(ChildKey),
+    /// There is already a child with this key
+    DuplicateKey(ChildKey),
+    /// There is no child at this index
+    OutOfBounds(usize),
 }
 
 //#[derive(Clone, Debug, PartialEq)]
 pub enum RenderableCommand {
-    Delete(Option<Node>),
+    Delete(Option<SceneNode>),
 }
 
 pub struct Renderable {
     pub spatial: Option<GDSpatial>,
     pub transform: Position,
+    /// If this is set the renderable is placed in 3D with it and transform is ignored, 3D models without one have transform laid flat on the ground
+    pub transform_3d: Option<Position3D>,
 
     pub renderable_id: Option<usize>,
     pub template: Option<Template>,
 
-    pub(crate) container_node: Option<Node>,
-    pub(crate) children_node: Option<Node>,
+    pub(crate) container_node: Option<SceneNode>,
+    pub(crate) children_node: Option<SceneNode>,
+    /// The entity whose container this renderable's container is nested in, see Parent
+    pub(crate) nested_in: Option<Entity>,
 
-    pub(crate) children_containers: Vec<Node>,
+    pub(crate) children_containers: Vec<SceneNode>,
 
-    pub(crate) renderable_node: Option<Node>,
+    pub(crate) renderable_node: Option<SceneNode>,
 
     pub(crate) children: Vec<Renderable>,
-    pub(crate) orphans: Vec<Option<Node>>,
+    pub(crate) child_keys: Vec<ChildKey>,
+    pub(crate) next_unnamed: u64,
+    pub(crate) orphans: Vec<Option<SceneNode>>,
 }
 
 unsafe impl Send for Renderable {}
@@ -59,16 +390,20 @@ impl Default for Renderable {
         Renderable {
             spatial: Some(GDSpatial::new()),
             transform: Position::default(),
+            transform_3d: None,
 
             renderable_id: None,
             template: None,
 
             container_node: None,
             children_node: None,
+            nested_in: None,
             children_containers: vec![],
             renderable_node: None,
 
             children: vec![],
+            child_keys: vec![],
+            next_unnamed: 0,
             orphans: vec![],
         }
     }
@@ -83,6 +418,27 @@ impl Renderable {
         res
     }
 
+    pub fn new_3d(transform: Position3D, renderable_id: usize, template: Template) -> Self {
+        let mut res = Renderable::default();
+        res.transform_3d = Some(transform);
+        res.renderable_id = Some(renderable_id);
+        res.template = Some(template);
+        res
+    }
+
+    /// Adds a child with a key and returns the renderable so that a whole tree can be declared at once,
+    /// a child that already has the key is replaced
+    pub fn with_child<K>(mut self, key: K, child: Renderable) -> Self
+        where K: Into<ChildKey> {
+        let key = key.into();
+        if let Some(index) = self.index_of(&key) {
+            self.orphan(index);
+        }
+        self.child_keys.push(key);
+        self.children.push(child);
+        self
+    }
+
     pub fn get_children(&self) -> &Vec<Renderable> {
         &self.children
     }
@@ -91,6 +447,126 @@ impl Renderable {
         &mut self.children[..]
     }
 
+    /// The key of every child in the same order as get_children(), children added without a key are given a ChildKey::Unnamed
+    pub fn child_keys(&self) -> &[ChildKey] {
+        &self.child_keys
+    }
+
+    /// Iterates over the children along with their keys
+    pub fn children_by_key(&self) -> impl Iterator<Item = (&ChildKey, &Renderable)> {
+        self.child_keys.iter().zip(self.children.iter())
+    }
+
+    pub fn children_by_key_mut(&mut self) -> impl Iterator<Item = (&ChildKey, &mut Renderable)> {
+        self.child_keys.iter().zip(self.children.iter_mut())
+    }
+
+    pub fn has_child<K>(&self, key: K) -> bool
+        where K: Into<ChildKey> {
+        self.index_of(&key.into()).is_some()
+    }
+
+    /// # Errors
+    /// 
+    /// This returns an error if there is no child with the key
+    pub fn try_child<K>(&self, key: K) -> Result<&Renderable, ChildError>
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.index_of(&key) {
+            Some(index) => Ok(&self.children[index]),
+            None => Err(ChildError::MissingKey(key)),
+        }
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if there is no child with the key
+    pub fn child<K>(&self, key: K) -> &Renderable
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.try_child(key.clone()) {
+            Ok(value) => value,
+            Err(_) => panic!("Attempt to access Renderable in MultiRenderable key {:?} failed", key),
+        }
+    }
+
+    /// # Errors
+    /// 
+    /// This returns an error if there is no child with the key
+    pub fn try_child_mut<K>(&mut self, key: K) -> Result<&mut Renderable, ChildError>
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.index_of(&key) {
+            Some(index) => Ok(&mut self.children[index]),
+            None => Err(ChildError::MissingKey(key)),
+        }
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if there is no child with the key
+    pub fn child_mut<K>(&mut self, key: K) -> &mut Renderable
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.try_child_mut(key.clone()) {
+            Ok(value) => value,
+            Err(_) => panic!("Attempt to access Renderable in MultiRenderable key {:?} failed", key),
+        }
+    }
+
+    /// Adds a child with a key after the other children
+    /// 
+    /// # Errors
+    /// 
+    /// This returns an error if there is already a child with the key
+    pub fn try_add_child<K>(&mut self, key: K, item: Renderable) -> Result<(), ChildError>
+        where K: Into<ChildKey> {
+        let key = key.into();
+        if self.index_of(&key).is_some() {
+            return Err(ChildError::DuplicateKey(key));
+        }
+        self.child_keys.push(key);
+        self.children.push(item);
+        Ok(())
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if there is already a child with the key
+    pub fn add_child<K>(&mut self, key: K, item: Renderable)
+        where K: Into<ChildKey> {
+        let key = key.into();
+        if self.try_add_child(key.clone(), item).is_err() {
+            panic!("Attempt to add Renderable to MultiRenderable key {:?} failed, the key is already in use", key);
+        }
+    }
+
+    /// Removes the child with the key, the keys of the other children are left as they are
+    /// 
+    /// # Errors
+    /// 
+    /// This returns an error if there is no child with the key
+    pub fn try_remove_child_by_key<K>(&mut self, key: K) -> Result<Renderable, ChildError>
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.index_of(&key) {
+            Some(index) => Ok(self.orphan(index)),
+            None => Err(ChildError::MissingKey(key)),
+        }
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if there is no child with the key
+    pub fn remove_child_by_key<K>(&mut self, key: K) -> Renderable
+        where K: Into<ChildKey> {
+        let key = key.into();
+        match self.try_remove_child_by_key(key.clone()) {
+            Ok(value) => value,
+            Err(_) => panic!("Attempt to remove Renderable in MultiRenderable key {:?} failed", key),
+        }
+    }
+
     pub fn try_get_child_mut(&mut self, index: usize) -> Option<&mut Renderable> {
         self.children.get_mut(index)
     }
@@ -113,22 +589,109 @@ impl Renderable {
         } 
     }
 
+    /// Removing a child shifts the index of every child after it, use keys if children need to be found again later
+    /// 
+    /// # Errors
+    /// 
+    /// This returns an error if the index is out of bounds
+    pub fn try_remove_child(&mut self, index: usize) -> Result<Renderable, ChildError> {
+        if index >= self.children.len() {
+            return Err(ChildError::OutOfBounds(index));
+        }
+        Ok(self.orphan(index))
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if the index is out of bounds
     pub fn remove_child(&mut self, index: usize) {
-        self.orphans.push(self.children.get(index).unwrap().renderable_node);
-        self.children.remove(index);
+        if self.try_remove_child(index).is_err() {
+            panic!("Attempt to remove Renderable in MultiRenderable index {} failed", index);
+        }
     }
 
-    pub fn insert_child(&mut self, index: usize, item: Renderable) {
+    /// The child is given a ChildKey::Unnamed
+    /// 
+    /// # Errors
+    /// 
+    /// This returns an error if the index is greater than the number of children
+    pub fn try_insert_child(&mut self, index: usize, item: Renderable) -> Result<(), ChildError> {
+        if index > self.children.len() {
+            return Err(ChildError::OutOfBounds(index));
+        }
+        let key = self.unnamed_key();
+        self.child_keys.insert(index, key);
         self.children.insert(index, item);
+        Ok(())
+    }
+
+    /// # Panics
+    /// 
+    /// This panics if the index is greater than the number of children
+    pub fn insert_child(&mut self, index: usize, item: Renderable) {
+        if self.try_insert_child(index, item).is_err() {
+            panic!("Attempt to insert Renderable in MultiRenderable index {} failed", index);
+        }
     }
 
+    /// The child is given a ChildKey::Unnamed
     pub fn push_child(&mut self, item: Renderable) {
+        let key = self.unnamed_key();
+        self.child_keys.push(key);
         self.children.push(item);
     }
 
     pub fn pop_child(&mut self) -> Option<Renderable> {
-        self.orphans.push(self.renderable_node);
-        self.children.pop()
+        if self.children.is_empty() {
+            return None;
+        }
+        Some(self.orphan(self.children.len() - 1))
+    }
+
+    fn index_of(&self, key: &ChildKey) -> Option<usize> {
+        self.child_keys.iter().position(|other| other == key)
+    }
+
+    fn unnamed_key(&mut self) -> ChildKey {
+        self.next_unnamed += 1;
+        ChildKey::Unnamed(self.next_unnamed - 1)
+    }
+
+    /// Removes the child and queues its nodes to be freed the next time the renderable is synced
+    fn orphan(&mut self, index: usize) -> Renderable {
+        self.child_keys.remove(index);
+        let mut child = self.children.remove(index);
+        self.orphans.push(child.container_node.take());
+        child.forget_nodes();
+        child
+    }
+
+    /// Clears the handles of nodes that have been freed so that the tree is instanced fresh if it is added again
+    fn forget_nodes(&mut self) {
+        self.container_node = None;
+        self.children_node = None;
+        self.renderable_node = None;
+        self.nested_in = None;
+        self.children_containers.clear();
+        self.orphans.clear();
+        self.spatial = self.spatial.map(|_| GDSpatial::new());
+        for child in self.children.iter_mut() {
+            child.forget_nodes();
+        }
+    }
+
+    /// Copies the renderable tree without any of its nodes, the copy gets instanced from scratch the next time it is synced
+    pub(crate) fn detached_clone(&self) -> Renderable {
+        let mut res = Renderable::default();
+        res.spatial = self.spatial.map(|_| GDSpatial::new());
+        res.transform = self.transform;
+        res.transform_3d = self.transform_3d;
+        res.renderable_id = self.renderable_id;
+        res.template = self.template;
+        res.children = self.children.iter().map(|child| child.detached_clone()).collect();
+        res.child_keys = self.child_keys.clone();
+        res.next_unnamed = self.next_unnamed;
+        res
     }
 }
 
@@ -136,6 +699,7 @@ impl Renderable {
 pub struct GDSpatial {
     pub(crate) prev_id: Option<usize>,
     pub(crate) prev_pos: Option<Position>,
+    pub(crate) prev_pos_3d: Option<Position3D>,
 }
 
 impl GDSpatial {
@@ -143,6 +707,7 @@ impl GDSpatial {
         GDSpatial {
             prev_id: None,
             prev_pos: None,
+            prev_pos_3d: None,
         }
     }
 
@@ -159,7 +724,7 @@ impl GDSpatial {
 
     pub(crate) fn is_pos_dirty(&self, renderable: &Renderable) -> bool {
         if let Some(prev_pos) = self.prev_pos {
-            if prev_pos != renderable.transform {
+            if prev_pos != renderable.transform || self.prev_pos_3d != renderable.transform_3d {
                 return true
             }
             return false;
@@ -167,4 +732,106 @@ impl GDSpatial {
             return true;
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f32) -> Renderable {
+        let mut renderable = Renderable::default();
+        renderable.transform = Position::new(x, 0f32);
+        renderable
+    }
+
+    #[test]
+    fn to_transform2d_rotates_around_the_pivot() {
+        let transform = Position::new(10f32, 10f32)
+            .with_pivot(5f32, 0f32)
+            .with_rotation(euclid::Angle::degrees(90f32))
+            .to_transform2d();
+        let pivot = transform.transform_point(euclid::point2(5f32, 0f32));
+        assert!((pivot.x - 10f32).abs() < 1e-4 && (pivot.y - 10f32).abs() < 1e-4);
+        let corner = transform.transform_point(euclid::point2(0f32, 0f32));
+        assert!((corner.x - 10f32).abs() < 1e-4 && (corner.y - 5f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn to_transform2d_scales_around_the_pivot() {
+        let transform = Position::new(0f32, 0f32).with_pivot(2f32, 2f32).with_scale(3f32, 3f32).to_transform2d();
+        let pivot = transform.transform_point(euclid::point2(2f32, 2f32));
+        assert!(pivot.x.abs() < 1e-4 && pivot.y.abs() < 1e-4);
+        let corner = transform.transform_point(euclid::point2(3f32, 2f32));
+        assert!((corner.x - 3f32).abs() < 1e-4 && corner.y.abs() < 1e-4);
+    }
+
+    #[test]
+    fn to_transform2d_without_a_pivot_is_offset_by_the_position() {
+        let transform = Position::new(3f32, 4f32).with_scale(2f32, -1f32).to_transform2d();
+        assert_eq!((transform.m31, transform.m32), (3f32, 4f32));
+        assert_eq!((transform.m11, transform.m22), (2f32, -1f32));
+    }
+
+    #[test]
+    fn from_transform2d_gives_back_the_same_transform() {
+        let transform = Position::new(3f32, 4f32)
+            .with_rotation(euclid::Angle::degrees(30f32))
+            .with_skew(euclid::Angle::degrees(10f32))
+            .with_scale(2f32, -1f32)
+            .with_pivot(1f32, 1f32)
+            .to_transform2d();
+        let again = Position::from_transform2d(&transform).to_transform2d();
+        for (a, b) in transform.to_row_major_array().iter().zip(again.to_row_major_array().iter()) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn from_transform_gives_back_the_same_transform() {
+        let transform = Position3D::new(1f32, 2f32, 3f32)
+            .with_euler(Vector3::new(0.3f32, -1.2f32, 2.5f32))
+            .with_scale(2f32, 1f32, 0.5f32)
+            .to_transform();
+        let again = Position3D::from_transform(&transform).to_transform();
+        assert_eq!(again.origin, transform.origin);
+        for (a, b) in transform.basis.elements.iter().zip(again.basis.elements.iter()) {
+            assert!((*a - *b).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn children_are_found_by_key_after_others_are_removed() {
+        let mut renderable = Renderable::default()
+            .with_child("body", at(1f32))
+            .with_child("turret", at(2f32))
+            .with_child(3, at(3f32));
+        renderable.push_child(at(4f32));
+
+        renderable.remove_child_by_key("body");
+        assert_eq!(renderable.child("turret").transform.x, 2f32);
+        assert_eq!(renderable.child(3).transform.x, 3f32);
+        assert_eq!(renderable.child(3u64).transform.x, 3f32);
+        assert!(!renderable.has_child("body"));
+        assert_eq!(renderable.try_child("body").err(), Some(ChildError::MissingKey("body".into())));
+    }
+
+    #[test]
+    fn duplicate_keys_are_rejected() {
+        let mut renderable = Renderable::default().with_child("turret", at(1f32));
+        assert_eq!(renderable.try_add_child("turret", at(2f32)), Err(ChildError::DuplicateKey("turret".into())));
+        assert_eq!(renderable.try_remove_child(5).err(), Some(ChildError::OutOfBounds(5)));
+        assert_eq!(renderable.child("turret").transform.x, 1f32);
+    }
+
+    #[test]
+    #[should_panic(expected = "Attempt to access Renderable in MultiRenderable key")]
+    fn missing_keys_panic_with_the_key() {
+        Renderable::default().child("turret");
+    }
+
+    #[test]
+    fn negative_ids_wrap_around_to_the_same_key() {
+        assert_eq!(ChildKey::from(-1i32), ChildKey::from(-1i64));
+        assert_eq!(ChildKey::from(-1i32), ChildKey::from(std::u64::MAX));
+    }
 }
\ No newline at end of file
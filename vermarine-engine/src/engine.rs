@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use std::collections::HashMap;
 
 /// The TransResource is used to send State transitions from within States and Systems
 /// ### Writing to the TransResource
@@ -13,7 +14,7 @@ use crate::prelude::*;
 /// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
 ///     // Sending a push
 ///     let sender = resources.get::<TransResource>().unwrap();
-///     sender.trans.try_send(Box::from(|| Trans::Push(Box::new( /* State goes here */ )))).ok();
+///     sender.send(|| Trans::Push(Box::new( /* State goes here */ ))).unwrap();
 /// }
 /// ```
 /// or for sending a pop
@@ -21,7 +22,7 @@ use crate::prelude::*;
 /// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
 ///     // Sending a pop
 ///     let sender = resources.get::<TransResource>().unwrap();
-///     sender.trans.try_send(Box::from(|| Trans::Pop)).ok();
+///     sender.send(|| Trans::Pop).unwrap();
 /// }
 /// ```
 /// 
@@ -31,7 +32,7 @@ use crate::prelude::*;
 ///     .write_resource::<TransResource>()
 ///     .build(move |commands, world, resources, queries| {
 ///         // Sending a push
-///         resources.trans.try_send(Box::from(|| Trans::Push(Box::new( /* State goes here */ )))).ok();
+///         resources.send(|| Trans::Push(Box::new( /* State goes here */ ))).unwrap();
 ///     })
 /// ```
 /// or for sending a pop
@@ -40,193 +41,1273 @@ use crate::prelude::*;
 ///     .write_resource::<TransResource>()
 ///     .build(move |commands, world, resources, queries| {
 ///         // Sending a pop
-///         resources.trans.try_send(Box::from(|| Trans::Pop)).ok();
+///         resources.send(|| Trans::Pop).unwrap();
 ///     })
 /// ```
-/// If you attempt to send more than one Trans only the first one will be used
+/// ### Ordering
+/// Every Trans sent during a frame is queued and they are all run after the states have been updated.
+/// Transitions run from the highest priority to the lowest, transitions with the same priority run in the order they were sent.
+/// send() uses a priority of 0, use send_with_priority() to run a transition before or after the others.
+/// 
+/// Any Trans sent while the queue is being run (e.g. from on_push) is run on the next frame.
+/// ### Layers
+/// send() runs the Trans on the layer of the states that are currently being updated, 
+/// so a state in the "ui" layer that sends a Trans::Pop pops the top of the "ui" layer.
+/// Use send_to() to run a Trans on a different layer.
+/// ```
+/// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     let sender = resources.get::<TransResource>().unwrap();
+///     sender.send_to("ui", || Trans::Push(Box::new(InventoryState { }))).unwrap();
+/// }
+/// ```
+/// ### Rejected transitions
+/// A Trans that can't be run, like a Trans::Pop on an empty stack or a Trans::PopTo with no matching state, is skipped
+/// and shows up in rejected() until the next time the queue is run.
+/// A Trans::Sequence stops at the first Trans that can't be run, the Trans before it are not undone.
+/// ```
+/// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     let sender = resources.get::<TransResource>().unwrap();
+///     for rejected in sender.rejected() {
+///         println!("{} on layer {} was rejected: {:?}", rejected.trans, rejected.layer, rejected.error);
+///     }
+/// }
+/// ```
 pub struct TransResource {
-    pub trans: crossbeam_channel::Sender<Box<(dyn FnOnce() -> Trans + 'static)>>,
+    pub(crate) trans: crossbeam_channel::Sender<QueuedTrans>,
+    pub(crate) layer: String,
+    pub(crate) rejected: Vec<RejectedTrans>,
 }
 
 unsafe impl Sync for TransResource {}
 unsafe impl Send for TransResource {}
 
+impl TransResource {
+    /// Queues a Trans to be run at the end of this frame
+    /// 
+    /// # Errors
+    /// 
+    /// This can return an error if the engine that owns this TransResource no longer exists
+    pub fn send<F>(&self, trans: F) -> Result<(), TransError> 
+        where F: FnOnce() -> Trans + 'static {
+        self.send_with_priority(0, trans)
+    }
+
+    /// Queues a Trans to be run at the end of this frame, higher priorities are run first
+    /// 
+    /// # Errors
+    /// 
+    /// This can return an error if the engine that owns this TransResource no longer exists
+    pub fn send_with_priority<F>(&self, priority: i32, trans: F) -> Result<(), TransError> 
+        where F: FnOnce() -> Trans + 'static {
+        self.queue(&self.layer, priority, Box::new(trans))
+    }
+
+    /// Queues a Trans to be run on the given layer at the end of this frame, if there is no layer with that name the Trans is rejected
+    /// 
+    /// # Errors
+    /// 
+    /// This can return an error if the engine that owns this TransResource no longer exists
+    pub fn send_to<F>(&self, layer: &str, trans: F) -> Result<(), TransError> 
+        where F: FnOnce() -> Trans + 'static {
+        self.send_to_with_priority(layer, 0, trans)
+    }
+
+    pub fn send_to_with_priority<F>(&self, layer: &str, priority: i32, trans: F) -> Result<(), TransError> 
+        where F: FnOnce() -> Trans + 'static {
+        self.queue(layer, priority, Box::new(trans))
+    }
+
+    /// The name of the layer that send() targets
+    pub fn layer(&self) -> &str {
+        &self.layer
+    }
+
+    /// Every Trans that was skipped because it couldn't be run, this is cleared each time the queue is run
+    pub fn rejected(&self) -> &[RejectedTrans] {
+        &self.rejected
+    }
+
+    fn queue(&self, layer: &str, priority: i32, trans: Box<(dyn FnOnce() -> Trans + 'static)>) -> Result<(), TransError> {
+        self.trans.send(QueuedTrans { layer: layer.into(), priority, trans }).map_err(|_| TransError::Disconnected)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransError {
+    /// The engine that owns the transition queue no longer exists
+    Disconnected,
+    /// There was no state on the stack to pop
+    EmptyStack,
+    /// A Trans::PopN asked for more states than there are on the stack
+    NotEnoughStates { requested: usize, depth: usize },
+    /// No state on the stack matched a Trans::PopTo or Trans::PopUntil
+    NoMatchingState,
+    /// There is no layer with this name
+    UnknownLayer(String),
+    /// A Trans::Transfer used a stack index that isn't in the stack, or the same index twice
+    NoSuchState(usize),
+    /// A Trans::LoadThen was run while another one was still loading
+    AlreadyLoading,
+    /// The ModelLoads of a Trans::LoadThen were not made from ModelLoad\<T> with the T of VermarineEngine\<T>
+    WrongKeyType,
+}
+
+/// A Trans that the engine skipped, see TransResource::rejected()
+#[derive(Clone, Debug, PartialEq)]
+pub struct RejectedTrans {
+    /// The layer the Trans was run on
+    pub layer: String,
+    /// The Debug output of the Trans
+    pub trans: String,
+    pub error: TransError,
+    /// How many Trans of a Trans::Sequence ran before the rejected one, these stay applied.
+    /// This is always 0 when the rejected Trans isn't a Trans::Sequence
+    pub applied: usize,
+}
+
+pub(crate) struct QueuedTrans {
+    pub(crate) layer: String,
+    pub(crate) priority: i32,
+    pub(crate) trans: Box<(dyn FnOnce() -> Trans + 'static)>,
+}
+
+/// The StackResource is a read only view of the state stacks that is kept up to date by the engine,
+/// it lets systems check which states are on the stack without access to the engine.
+///
+/// depth(), names(), top() and contains() look at the layer of the states that are currently being updated.
+/// ```
+/// SystemBuilder::<()>::new("ExampleSystem")
+///     .read_resource::<StackResource>()
+///     .build(move |commands, world, stack, queries| {
+///         if stack.contains("Pause") {
+///             // --snip
+///         }
+///     })
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StackResource {
+    layers: Vec<(String, Vec<String>)>,
+    current: usize,
+}
+
+impl StackResource {
+    /// The number of states on the stack
+    pub fn depth(&self) -> usize {
+        self.names().len()
+    }
+
+    /// The get_name of every state on the stack from the bottom of the stack to the top
+    pub fn names(&self) -> &[String] {
+        match self.layers.get(self.current) {
+            Some((_, names)) => &names[..],
+            None => &[],
+        }
+    }
+
+    /// The get_name of the state at the top of the stack
+    pub fn top(&self) -> Option<&str> {
+        self.names().last().map(|name| name.as_str())
+    }
+
+    /// Returns true if a state with the given get_name is on the stack
+    pub fn contains(&self, name: &str) -> bool {
+        self.names().iter().any(|state| state == name)
+    }
+
+    /// The name of the layer that the other methods look at
+    pub fn layer(&self) -> Option<&str> {
+        self.layers.get(self.current).map(|(layer, _)| layer.as_str())
+    }
+
+    /// The name of every layer from the bottom layer to the top
+    pub fn layers(&self) -> Vec<&str> {
+        self.layers.iter().map(|(layer, _)| layer.as_str()).collect()
+    }
+
+    /// The get_name of every state in the given layer from the bottom of its stack to the top
+    pub fn layer_names(&self, layer: &str) -> Option<&[String]> {
+        self.layers.iter().find(|(name, _)| name == layer).map(|(_, names)| &names[..])
+    }
+}
+
+/// The GlobalWorld resource holds a world that is owned by the engine instead of a state, it survives every Trans
+/// so it's the place for entities such as a HUD, a music player or the player's profile.
+///
+/// Entities with a Renderable are synced to godot every frame under their own container node which is always drawn above the states.
+///
+/// Example of querying the global world from inside of a state's system:
+/// ```
+/// SystemBuilder::<()>::new("HudSystem")
+///     .write_resource::<GlobalWorld>()
+///     .with_query(<Read<Health>>::query())
+///     .build(move |commands, world, global, query| {
+///         for health in query.iter(&mut *world) {
+///             for mut hud in <Write<HealthBar>>::query().iter_mut(&mut global.world) {
+///                 // --snip
+///             }
+///         }
+///     })
+/// ```
+pub struct GlobalWorld {
+    pub world: LWorld,
+}
+
+unsafe impl Sync for GlobalWorld {}
+unsafe impl Send for GlobalWorld {}
+
+/// The engine's side of the GlobalWorld, the godot nodes and removal events that sync_world() needs
+pub(crate) struct GlobalLayer {
+    pub(crate) receiver: crossbeam_channel::Receiver<legion::event::Event>,
+    pub(crate) rootnode: SceneNode,
+    pub(crate) containernode: SceneNode,
+    pub(crate) node_lookup: HashMap<Entity, SceneNode>,
+}
+
+/// The order that the engine syncs the states in each layer to godot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncOrder {
+    /// The bottom of the stack is synced first, this is the default
+    BottomUp,
+    /// The top of the stack is synced first
+    TopDown,
+}
+
+impl Default for SyncOrder {
+    fn default() -> Self {
+        SyncOrder::BottomUp
+    }
+}
+
+/// The name of the layer that every engine starts with
+pub const DEFAULT_LAYER: &str = "game";
+
+/// A named state stack, every layer is updated and synced each frame and is drawn above the layers added before it
+pub(crate) struct Layer {
+    pub(crate) name: String,
+    pub(crate) rootnode: SceneNode,
+    pub(crate) states: Vec<(StateData, Box<dyn State>)>,
+}
+
 pub struct VermarineEngine<T> where 
     T: Eq + std::hash::Hash + 'static {
     universe: Universe,
-    states: Vec<(StateData, Box<dyn State>)>,
+    layers: Vec<Layer>,
+    current_layer: usize,
     pub resources: Resources,
-    owner: Node,
-    trans_receiver: crossbeam_channel::Receiver<Box<dyn FnOnce() -> Trans>>,
+    backend: Box<dyn SceneBackend>,
+    trans_receiver: crossbeam_channel::Receiver<QueuedTrans>,
+    finished: bool,
+    loading: Option<ActiveLoad<T>>,
+    sync_order: SyncOrder,
+    recording: Option<ActiveRecording>,
+    replay: Option<ActiveReplay>,
+    global: GlobalLayer,
     phantom: std::marker::PhantomData<T>,
 }
 
 impl<T> VermarineEngine<T> 
     where 
     T: Eq + std::hash::Hash + 'static {
+    /// Creates an engine that uses the GodotBackend to add nodes under the owner node
     pub fn new(owner: Node) -> Self {
+        VermarineEngine::with_backend(Box::new(GodotBackend::new(owner)))
+    }
+
+    /// Creates an engine that uses the HeadlessBackend, this lets states and systems run without godot.
+    /// 
+    /// Models\<T> has to be populated with Models::insert_headless() as load_scene() requires godot.
+    pub fn headless() -> Self {
+        VermarineEngine::with_backend(Box::new(HeadlessBackend::new()))
+    }
+
+    pub fn with_backend(mut backend: Box<dyn SceneBackend>) -> Self {
         let universe = Universe::new();
         let mut resources = Resources::default();
-        let (sender, receiver) = crossbeam_channel::bounded(1);
-        resources.insert::<>(TransResource { trans: sender });
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        resources.insert::<>(TransResource { trans: sender, layer: DEFAULT_LAYER.into(), rejected: vec![] });
+        resources.insert(InputState::default());
+        resources.insert(Time::default());
+        resources.insert(StackResource::default());
+        resources.insert(Random::new(rand::random()));
+        resources.insert(InputBindings::new(backend.read_bindings(), backend.user_dir()));
+
+        // Create the global world and its nodes
+        let mut world = universe.create_world();
+        let (event_sender, event_receiver) = crossbeam_channel::unbounded();
+        world.subscribe(event_sender, any());
+        resources.insert(GlobalWorld { world });
+
+        let root = backend.root();
+        let rootnode = backend.create_node(root, "Global", NodeKind::Node2D);
+        let containernode = backend.create_node(rootnode, "RenderablesContainer", NodeKind::Node2D);
+        let global = GlobalLayer {
+            receiver: event_receiver,
+            rootnode,
+            containernode,
+            node_lookup: HashMap::new(),
+        };
 
-        VermarineEngine {
+        let mut engine = VermarineEngine {
             universe,
-            states: Vec::new(),
+            layers: Vec::new(),
+            current_layer: 0,
             resources,
             trans_receiver: receiver,
+            finished: false,
+            loading: None,
+            sync_order: SyncOrder::default(),
+            recording: None,
+            replay: None,
+            global,
             phantom: std::marker::PhantomData,
-            owner: owner,
+            backend,
+        };
+        engine.add_layer(DEFAULT_LAYER);
+        engine
+    }
+
+    /// Adds a new empty layer that is drawn above all of the existing layers, nothing happens if there is already a layer with that name
+    /// ```
+    /// fn _ready(&mut self, owner: Node) {
+    ///     self.engine._ready(owner);
+    ///     self.engine.add_layer("ui");
+    ///     self.engine.push(Box::new(GameState { }));
+    ///     self.engine.select_layer("ui");
+    ///     self.engine.push(Box::new(HudState { }));
+    /// }
+    /// ```
+    pub fn add_layer(&mut self, name: &str) {
+        if self.layer_index(name).is_some() {
+            return;
+        }
+
+        let root = self.backend.root();
+        let rootnode = self.backend.create_node(root, &format!("Layer: {}", name), NodeKind::Node2D);
+        self.layers.push(Layer {
+            name: name.into(),
+            rootnode,
+            states: Vec::new(),
+        });
+        self.backend.raise(self.global.rootnode);
+        self.refresh_stack_resource();
+    }
+
+    /// Sets the layer that push(), pop() and the other stack methods act on, returns false if there is no layer with that name
+    pub fn select_layer(&mut self, name: &str) -> bool {
+        match self.layer_index(name) {
+            Some(index) => {
+                self.bind_layer(index);
+                true
+            },
+            None => false,
         }
     }
 
+    /// The name of the layer that push(), pop() and the other stack methods act on
+    pub fn current_layer(&self) -> &str {
+        &self.layers[self.current_layer].name
+    }
+
+    /// The name of every layer from the bottom layer to the top
+    pub fn layer_names(&self) -> Vec<String> {
+        self.layers.iter().map(|layer| layer.name.clone()).collect()
+    }
+
+    fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// Points the stack methods, TransResource::send() and the StackResource at the layer at index
+    fn bind_layer(&mut self, index: usize) {
+        self.current_layer = index;
+        if let Some(mut trans) = self.resources.get_mut::<TransResource>() {
+            trans.layer = self.layers[index].name.clone();
+        }
+        if let Some(mut stack) = self.resources.get_mut::<StackResource>() {
+            stack.current = index;
+        }
+    }
+
+    /// Runs f with the layer at index bound and then binds the previous layer again
+    fn with_layer<R, F>(&mut self, index: usize, f: F) -> R
+        where F: FnOnce(&mut Self) -> R {
+        let previous = self.current_layer;
+        self.bind_layer(index);
+        let result = f(self);
+        self.bind_layer(previous);
+        result
+    }
+
+    pub fn backend(&self) -> &dyn SceneBackend {
+        &*self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut dyn SceneBackend {
+        &mut *self.backend
+    }
+
+    /// Returns the backend as B if that is the type of backend the engine was created with
+    /// ```
+    /// let engine = VermarineEngine::<i32>::headless();
+    /// let tree = engine.backend_as::<HeadlessBackend>().unwrap();
+    /// ```
+    pub fn backend_as<B>(&self) -> Option<&B> 
+        where B: SceneBackend + 'static {
+        self.backend.as_any().downcast_ref::<B>()
+    }
+
+    pub fn backend_as_mut<B>(&mut self) -> Option<&mut B> 
+        where B: SceneBackend + 'static {
+        self.backend.as_any_mut().downcast_mut::<B>()
+    }
+
     pub fn _ready(&mut self, _owner: Node) {
-        godot_print!("Vermarine: HelloWorld");
+        self.backend.ready();
+        self.backend.print("Vermarine: HelloWorld");
+    }
+
+    /// Handles godot notifications, this needs to be hooked up for window close requests to reach the states.
+    /// 
+    /// Window close requests are sent to the on_quit_request method of the state at the top of the stack.
+    pub fn _notification(&mut self, _owner: Node, what: i64) {
+        if what == MainLoop::NOTIFICATION_WM_QUIT_REQUEST {
+            self.request_quit();
+        }
+    }
+
+    /// Runs the Trans returned by the on_quit_request method of the state at the top of the stack,
+    /// if the stack is empty the engine quits straight away.
+    pub fn request_quit(&mut self) {
+        let layer = match self.layers.iter().rposition(|layer| !layer.states.is_empty()) {
+            Some(layer) => layer,
+            None => {
+                self.quit();
+                return;
+            },
+        };
+
+        self.with_layer(layer, |engine| {
+            let (data, state) = engine.layers[engine.current_layer].states.last_mut().unwrap();
+            let trans = data.scoped(&mut engine.resources, |data, resources| state.on_quit_request(data, resources));
+            engine.run_state_trans(trans);
+        });
+    }
+
+    /// Returns true once a Trans::Quit has been run, after this the engine will no longer update or sync any states
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn _physics_process(&mut self, _owner: Node, delta: f64) {
+        self.tick(delta);
+    }
+
+    /// Runs a single frame of the engine, this is what _physics_process calls
+    pub fn tick(&mut self, delta: f64) {
+        if self.finished {
+            return;
+        }
+
+        // Take this frame's delta and input from the replay
+        let mut delta = delta;
+        let mut replay_input = None;
+        if let Some(replay) = self.replay.as_mut() {
+            match replay.next_frame() {
+                Some(frame) => {
+                    delta = frame.delta;
+                    replay_input = Some(frame.input.clone());
+                },
+                None => {
+                    self.replay = None;
+                    self.backend.print("Vermarine: Replay finished");
+                },
+            }
+        }
+        let unscaled_delta = delta;
+
+        // Advance time for this frame
+        let delta = match self.resources.get_mut::<Time>() {
+            Some(mut time) => {
+                time.advance(delta);
+                time.delta()
+            },
+            None => delta,
+        };
+
+        // Capture input for this frame
+        if let Some(mut input) = self.resources.get_mut::<InputState>() {
+            self.backend.poll_input(&mut input);
+            if let Some(replay_input) = replay_input {
+                input.apply_snapshot(&replay_input);
+            }
+            if let Some(recording) = self.recording.as_mut() {
+                if let Err(error) = recording.push(RecordedFrame { delta: unscaled_delta, input: input.snapshot() }) {
+                    self.backend.print(&error);
+                }
+            }
+        }
+
+        // Continue loading models for Trans::LoadThen
+        self.poll_loading();
+
+        // Work out the GlobalPositions of the global world before any schedule reads them
+        if let Some(mut global_world) = self.resources.get_mut::<GlobalWorld>() {
+            propagate_transforms(&mut global_world.world);
+        }
+
+        // Run methods on states in every layer from the top layer down
+        for layer in (0..self.layers.len()).rev() {
+            self.with_layer(layer, |engine| engine.update_layer(delta));
+        }
+
+        if let Some(mut input) = self.resources.get_mut::<InputState>() {
+            input.end_frame();
+        }
+
+        // Write any rebinding done this frame back to the backend
+        if let Some(mut bindings) = self.resources.get_mut::<InputBindings>() {
+            let changed = bindings.take_changed();
+            if !changed.is_empty() {
+                self.backend.write_bindings(&changed);
+            }
+        }
+
+        // Run every transition that was sent this frame
+        self.run_queued_trans();
+        if self.finished {
+            return;
+        }
+
+        // Sync every visible state to godot, states that aren't synced still free the nodes of removed entities
+        for layer in self.layers.iter_mut() {
+            let state_len = layer.states.len();
+            let order: Vec<usize> = match self.sync_order {
+                SyncOrder::BottomUp => (0..state_len).collect(),
+                SyncOrder::TopDown => (0..state_len).rev().collect(),
+            };
+
+            for i in order {
+                let state = layer.states.get_mut(i).unwrap();
+                if i == state_len - 1 || (!state.0.hidden && state.0.sync_when_covered) {
+                    sync_state::<T>(&mut self.resources, &mut *self.backend, state);
+                } else {
+                    sync_removals(&mut *self.backend, &mut state.0.world, state.0.containernode, &state.0.receiver, &mut state.0.node_lookup);
+                }
+            }
+        }
+        if self.layers.iter().all(|layer| layer.states.is_empty()) {
+            self.backend.print("Expected a state in the stack but one was not found");
+        }
+
+        // Sync the global world to godot
+        sync_global::<T>(&self.resources, &mut *self.backend, &mut self.global);
     }
 
-    pub fn _physics_process(&mut self, _owner: Node, _delta: f64) {
-        // Run methods on states in the stack
-        let state_len = self.states.len();
+    /// Runs update on the top of the current layer's stack and shadow_update on the states below it,
+    /// the GlobalPositions of every state's world are worked out first so that its systems see this frame's hierarchy.
+    /// Input consumed by a state is hidden from every state below it, including the states in lower layers
+    fn update_layer(&mut self, delta: f64) {
+        let state_len = self.layers[self.current_layer].states.len();
         for i in (0..state_len).rev() {
-            let (data, state) = self.states.get_mut(i).unwrap();
+            let (data, state) = self.layers[self.current_layer].states.get_mut(i).unwrap();
+            propagate_transforms(&mut data.world);
+
+            if let Some(mut input) = self.resources.get_mut::<InputState>() {
+                input.begin_state();
+            }
             
             if i == state_len - 1 {
-                state.update(data, &mut self.resources);
-            } else {
-                state.shadow_update(data, &mut self.resources); 
+                data.elapsed += delta;
+                data.scoped(&mut self.resources, |data, resources| {
+                    state.update(data, resources);
+                    if let Some(schedule) = &mut data.schedule {
+                        schedule.execute(&mut data.world, resources);
+                    }
+                });
+            } else if !data.frozen {
+                data.scoped(&mut self.resources, |data, resources| {
+                    state.shadow_update(data, resources); 
+                    if let Some(schedule) = &mut data.shadow_schedule {
+                        schedule.execute(&mut data.world, resources);
+                    }
+                });
+            }
+
+            if data.blocks_input {
+                if let Some(mut input) = self.resources.get_mut::<InputState>() {
+                    input.consume_all();
+                }
             }
         }
+    }
+
+    /// Starts recording the input and delta of every frame, the Random resource is reseeded so that the recording can be replayed.
+    /// Any recording that was already in progress is thrown away
+    pub fn start_recording(&mut self) {
+        let seed = self.reseed_for_recording();
+        self.recording = Some(ActiveRecording { recording: InputRecording::new(seed), file: None });
+    }
+
+    /// Starts recording the same way start_recording() does, each frame is written to the file at the path as soon as it is recorded
+    /// so the recording survives the game crashing
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if the file could not be created, nothing is recorded if it couldn't be
+    pub fn record_to(&mut self, path: &str) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        let seed = self.reseed_for_recording();
+        std::io::Write::write_all(&mut file, InputRecording::header_text(seed).as_bytes())?;
+        self.recording = Some(ActiveRecording { recording: InputRecording::new(seed), file: Some((path.into(), file)) });
+        Ok(())
+    }
 
-        // Run a transition if one was sent
-        if let Ok(trans) = self.trans_receiver.try_recv() {
-            let trans = trans();
-            self.run_state_trans(trans);
+    fn reseed_for_recording(&mut self) -> u64 {
+        let seed = rand::random();
+        if let Some(mut random) = self.resources.get_mut::<Random>() {
+            random.reseed(seed);
         }
+        seed
+    }
 
-        // Sync the top of the stack's state to godot
-        let state_len = self.states.len();
-        if state_len > 0 {
-            let state = self.states.get_mut(state_len - 1).unwrap();
-            sync_state::<T>(&mut self.resources, state);
-        } else {
-            godot_print!("Expected a state in the stack but one was not found");
+    /// Stops recording and returns the recording, this returns None if nothing was being recorded
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        self.recording.take().map(|recording| recording.recording)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Reseeds the Random resource with the recording's seed, then each frame uses the recording's delta and input instead of the player's until it runs out.
+    /// The replay should be started from the same point in the game that the recording was started from
+    pub fn start_replay(&mut self, recording: InputRecording) {
+        if let Some(mut random) = self.resources.get_mut::<Random>() {
+            random.reseed(recording.seed);
+        }
+        self.replay = Some(ActiveReplay { recording, cursor: 0 });
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Starts recording or replaying based on SessionMode::from_env(), call this before pushing the first state
+    /// ```
+    /// fn _init(owner: Node) -> Self {
+    ///     let mut instance = HelloWorld { engine: VermarineEngine::<i32>::new(owner) };
+    ///     instance.engine.configure_from_env().unwrap();
+    ///     // --snip
+    /// }
+    /// ```
+    /// # Errors
+    ///
+    /// This returns an error if the recording could not be created or the replay could not be loaded
+    pub fn configure_from_env(&mut self) -> std::io::Result<SessionMode> {
+        let mode = SessionMode::from_env();
+        match &mode {
+            SessionMode::Live => {},
+            SessionMode::Record(path) => self.record_to(path)?,
+            SessionMode::Replay(path) => self.start_replay(InputRecording::load(path)?),
+        }
+        Ok(mode)
+    }
+
+    /// Replaces the InputBindings resource with the bindings from the backend, any unsaved rebinding is lost
+    pub fn reload_bindings(&mut self) {
+        let bindings = InputBindings::new(self.backend.read_bindings(), self.backend.user_dir());
+        self.resources.insert(bindings);
+    }
+
+    pub fn sync_order(&self) -> SyncOrder {
+        self.sync_order
+    }
+
+    /// Sets the order that the states in each layer are synced to godot in
+    pub fn set_sync_order(&mut self, sync_order: SyncOrder) {
+        self.sync_order = sync_order;
+    }
+
+    /// Returns the RenderablesContainer node that the GlobalWorld's entities are instanced under
+    pub fn global_container(&self) -> SceneNode {
+        self.global.containernode
+    }
+
+    /// The number of states on the stack
+    pub fn depth(&self) -> usize {
+        self.layers[self.current_layer].states.len()
+    }
+
+    /// The get_name of every state on the stack from the bottom of the stack to the top
+    pub fn state_names(&self) -> Vec<String> {
+        self.layers[self.current_layer].states.iter().map(|(data, _)| data.name.clone()).collect()
+    }
+
+    /// Returns true if a state with the given get_name is on the stack
+    pub fn has_state(&self, name: &str) -> bool {
+        self.layers[self.current_layer].states.iter().any(|(data, _)| data.name == name)
+    }
+
+    /// Returns the StateData of the state at index in the stack, 0 is the bottom of the stack
+    pub fn state_data(&self, index: usize) -> Option<&StateData> {
+        self.layers[self.current_layer].states.get(index).map(|(data, _)| data)
+    }
+
+    pub fn state_data_mut(&mut self, index: usize) -> Option<&mut StateData> {
+        self.layers[self.current_layer].states.get_mut(index).map(|(data, _)| data)
+    }
+
+    /// Returns a description of every layer from the top layer down, with every state in the layer from the top down along with the systems in its schedules
+    /// ```text
+    /// Layer: game
+    /// State1: Printer
+    ///     schedule: Some(["InputSystem", "MoveSystem"])
+    ///     shadow_schedule: None
+    /// State0: Base
+    ///     schedule: None
+    ///     shadow_schedule: None
+    /// ```
+    pub fn debug_stack(&self) -> String {
+        let mut output = String::new();
+        for layer in self.layers.iter().rev() {
+            output.push_str(&format!("Layer: {}\n", layer.name));
+            for (i, (data, _)) in layer.states.iter().enumerate().rev() {
+                output.push_str(&format!("State{}: {}\n", i, data.name));
+                output.push_str(&format!("    schedule: {:?}\n", data.schedule));
+                output.push_str(&format!("    shadow_schedule: {:?}\n", data.shadow_schedule));
+            }
         }
+        output
     }
 
-    pub fn _input(_owner: Node, _event: Option<InputEvent>) {
+    /// Prints debug_stack() to the backend's console
+    pub fn print_stack(&mut self) {
+        let output = self.debug_stack();
+        self.backend.print(&output);
     }
 
+    /// Passes input events to the backend so that mouse and text input end up in the InputState resource
+    pub fn _input(&mut self, _owner: Node, event: Option<InputEvent>) {
+        if let (Some(event), Some(mut input)) = (event, self.resources.get_mut::<InputState>()) {
+            self.backend.input_event(&event, &mut input);
+        }
+    }
+
+    /// Runs every Trans that was sent before this was called in priority order, 
+    /// a sort is used that keeps Trans with the same priority in the order they were sent
+    pub(crate) fn run_queued_trans(&mut self) {
+        let mut queue: Vec<QueuedTrans> = self.trans_receiver.try_iter().collect();
+        queue.sort_by(|a, b| b.priority.cmp(&a.priority));
+        if let Some(mut sender) = self.resources.get_mut::<TransResource>() {
+            sender.rejected.clear();
+        }
+
+        for queued in queue.into_iter() {
+            if self.finished {
+                break;
+            }
+            let trans = (queued.trans)();
+            match self.layer_index(&queued.layer) {
+                Some(layer) => self.with_layer(layer, |engine| engine.run_state_trans(trans)),
+                None => self.reject(RejectedTrans {
+                    trans: format!("{:?}", trans),
+                    error: TransError::UnknownLayer(queued.layer.clone()),
+                    layer: queued.layer,
+                    applied: 0,
+                }),
+            }
+        }
+    }
+
+    /// Runs the Trans on the current layer, if it can't be run it is added to TransResource::rejected() instead
     pub(crate) fn run_state_trans(&mut self, trans: Trans) {
+        let description = format!("{:?}", trans);
+        let result = match trans {
+            Trans::Sequence(sequence) => self.try_run_sequence(sequence),
+            trans => self.try_run_state_trans(trans).map_err(|error| (error, 0)),
+        };
+        if let Err((error, applied)) = result {
+            let layer = self.layers[self.current_layer].name.clone();
+            self.reject(RejectedTrans { layer, trans: description, error, applied });
+        }
+    }
+
+    fn reject(&mut self, rejected: RejectedTrans) {
+        self.backend.print(&format!("{} on layer {} was rejected: {:?}", rejected.trans, rejected.layer, rejected.error));
+        if let Some(mut sender) = self.resources.get_mut::<TransResource>() {
+            sender.rejected.push(rejected);
+        }
+    }
+
+    /// Checks that the Trans can be run before running it, nothing is changed when an error is returned.
+    /// The exception is a Trans::Sequence, it runs up to the first Trans that can't be run and the Trans before that stay applied.
+    fn try_run_state_trans(&mut self, trans: Trans) -> Result<(), TransError> {
+        let depth = self.layers[self.current_layer].states.len();
         match trans {
             Trans::None => {},
             Trans::Push(state) => { self.push(state) },
+            Trans::Pop | Trans::PopWith(_) if depth == 0 => return Err(TransError::EmptyStack),
             Trans::Pop => { self.pop() },
+            Trans::PopWith(result) => { self.pop_with(result) },
+            Trans::PopN(count) if count > depth => return Err(TransError::NotEnoughStates { requested: count, depth }),
+            Trans::PopN(count) => { self.pop_n(count) },
+            Trans::PopTo(name) => if !self.pop_to(|state| state == name) { return Err(TransError::NoMatchingState) },
+            Trans::PopUntil(predicate) => if !self.pop_to(predicate) { return Err(TransError::NoMatchingState) },
             Trans::Switch(state) => { self.switch(state) },
             Trans::Replace(state) => { self.replace(state) },
             Trans::NewStack(stack) => { self.new_stack(stack) },
-            Trans::Sequence(sequence) => { self.sequence(sequence) },
-            Trans::Quit => {},
+            Trans::NewStackWith(stack, teardown) => { self.new_stack_with(stack, teardown) },
+            Trans::Sequence(sequence) => return self.try_run_sequence(sequence).map_err(|(error, _)| error),
+            Trans::Transfer(from, to, _) if from >= depth || from == to => return Err(TransError::NoSuchState(from)),
+            Trans::Transfer(_, to, _) if to >= depth => return Err(TransError::NoSuchState(to)),
+            Trans::Transfer(from, to, transfer) => { self.transfer(from, to, transfer); },
+            Trans::SwitchCarrying(state, transfer) => { self.switch_carrying(state, transfer) },
+            Trans::LoadThen(..) if self.loading.is_some() => return Err(TransError::AlreadyLoading),
+            Trans::LoadThen(loading, models, then) => match models.downcast::<T>() {
+                Some(models) => self.begin_load(loading, models, *then),
+                None => return Err(TransError::WrongKeyType),
+            },
+            Trans::OnLayer(layer, trans) => match self.layer_index(&layer) {
+                Some(layer) => return self.with_layer(layer, |engine| engine.try_run_state_trans(*trans)),
+                None => return Err(TransError::UnknownLayer(layer)),
+            },
+            Trans::Quit => { self.quit() },
+        }
+        Ok(())
+    }
+
+    /// Runs each Trans in order until one can't be run, the error comes with the number of Trans that did run
+    fn try_run_sequence(&mut self, sequence: Vec<Trans>) -> Result<(), (TransError, usize)> {
+        for (applied, trans) in sequence.into_iter().enumerate() {
+            if self.finished {
+                break;
+            }
+            self.try_run_state_trans(trans).map_err(|error| (error, applied))?;
+        }
+        Ok(())
+    }
+
+    /// Calls the on_cover method of the state at the top of the stack,
+    /// then calls the on_push method of the passed in state,
+    /// then pushes the passed in state onto the state stack.
+    pub fn push(&mut self, mut state: Box<dyn State>) {
+        // Send on_cover event to current top of stack if there is one
+        let state_len = self.layers[self.current_layer].states.len();
+        if state_len >= 1 {
+            let (data, state) = self.layers[self.current_layer].states.get_mut(state_len - 1).unwrap();
+            data.scoped(&mut self.resources, |data, resources| state.on_cover(data, resources));
+        }
+
+        // Create new world
+        let world = self.universe.create_world();
+        let mut data = StateData::new(world);
+
+        data.resources.enter(&mut self.resources);
+
+        // Create rootnode
+        let name = state.get_name(&mut data, &mut self.resources);
+        data.name = name.clone();
+        let root = self.layers[self.current_layer].rootnode;
+        let rootnode = self.backend.create_node(root, &format!("State{}: {}", state_len, name), NodeKind::Node2D);
+        data.rootnode = Some(rootnode);
+
+        // Create containernode
+        let containernode = self.backend.create_node(rootnode, "RenderablesContainer", NodeKind::Node2D);
+        data.containernode = Some(containernode);
+
+        // Create statenode
+        if let Some(index) = state.is_node(&mut data, &mut self.resources) {
+            let models = self.resources.get::<Models<T>>().unwrap();
+            data.statenode = instance_model(&mut *self.backend, &models, rootnode, "StateNode", index);
+        }
+
+        // Create schedules
+        data.schedule = state.schedule(&mut data, &mut self.resources);
+        data.shadow_schedule = state.shadow_schedule(&mut data, &mut self.resources);
+        data.cover_policy = state.cover_policy(&mut data, &mut self.resources);
+        data.sync_when_covered = state.sync_when_covered(&mut data, &mut self.resources);
+        data.blocks_input = state.blocks_input(&mut data, &mut self.resources);
+
+        // Actually push state onto the stack
+        state.on_push(&mut data, &mut self.resources);
+        data.resources.exit(&mut self.resources);
+        self.layers[self.current_layer].states.push((data, state));
+        self.stack_changed();
+    }
+
+    /// Calls the on_pop method of the state at the top of the stack,
+    /// then calls the on_uncover method of the state below the state at the top of the stack,
+    /// then pops the state at the top of the stack.
+    pub fn pop(&mut self) {
+        self.pop_inner(None);
+    }
+
+    /// Calls the on_pop method of the state at the top of the stack,
+    /// then calls the on_uncover_with method of the state below the state at the top of the stack with the passed in result,
+    /// then pops the state at the top of the stack.
+    pub fn pop_with(&mut self, result: Box<dyn std::any::Any>) {
+        self.pop_inner(Some(result));
+    }
+
+    fn pop_inner(&mut self, result: Option<Box<dyn std::any::Any>>) {
+        if self.teardown_top() {
+            if let Some((data, state)) = self.layers[self.current_layer].states.last_mut() {
+                data.scoped(&mut self.resources, |data, resources| match result {
+                    Some(result) => state.on_uncover_with(data, resources, result),
+                    None => state.on_uncover(data, resources),
+                });
+            }
+            self.stack_changed();
+        }
+    }
+
+    /// Pops count states off of the top of the stack calling on_pop on each of them,
+    /// then calls the on_uncover method of the new top of the stack.
+    /// The states in between are not uncovered as they are popped.
+    pub fn pop_n(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+
+        for _ in 0..count {
+            self.teardown_top();
+        }
+
+        if let Some((data, state)) = self.layers[self.current_layer].states.last_mut() {
+            data.scoped(&mut self.resources, |data, resources| state.on_uncover(data, resources));
+        }
+        self.stack_changed();
+    }
+
+    /// Pops states off of the top of the stack until the get_name of the top state matches the predicate, see pop_n().
+    /// If no state on the stack matches then nothing is popped and false is returned.
+    pub fn pop_to<F>(&mut self, predicate: F) -> bool
+        where F: Fn(&str) -> bool {
+        match self.layers[self.current_layer].states.iter().rposition(|(data, _)| predicate(&data.name)) {
+            Some(index) => {
+                self.pop_n(self.layers[self.current_layer].states.len() - 1 - index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Every state that leaves the stack goes through here.
+    /// Removes the state at the top of the stack, calls its on_pop method and then frees its godot nodes and clears its node lookup.
+    /// Returns false if the stack was empty.
+    fn teardown_top(&mut self) -> bool {
+        match self.layers[self.current_layer].states.len() {
+            0 => false,
+            len => self.teardown_at(len - 1),
+        }
+    }
+
+    /// The same as teardown_top() for a state anywhere in the stack, the states above it move down by one
+    fn teardown_at(&mut self, index: usize) -> bool {
+        if index >= self.layers[self.current_layer].states.len() {
+            return false;
+        }
+
+        let (mut data, mut state) = self.layers[self.current_layer].states.remove(index);
+        data.scoped(&mut self.resources, |data, resources| state.on_pop(data, resources));
+        free_state_nodes(&mut *self.backend, &mut data);
+
+        // Tearing down the loading state cancels the load, otherwise the load would pop whatever state ends up at its index
+        let cancel = match self.loading.as_mut() {
+            Some(load) if load.layer == self.current_layer => {
+                if load.state_index > index {
+                    load.state_index -= 1;
+                }
+                load.state_index == index
+            },
+            _ => false,
+        };
+        if cancel {
+            self.loading = None;
+        }
+        true
+    }
+
+    /// Called whenever states are added or removed from the stack
+    fn stack_changed(&mut self) {
+        self.apply_cover_policies();
+        self.refresh_stack_resource();
+    }
+
+    fn refresh_stack_resource(&mut self) {
+        let layers = self.layers.iter()
+            .map(|layer| (layer.name.clone(), layer.states.iter().map(|(data, _)| data.name.clone()).collect()))
+            .collect();
+        self.resources.insert(StackResource { layers, current: self.current_layer });
+    }
+
+    /// Hides and freezes every state in the stack based on the cover policies of the states above it
+    pub(crate) fn apply_cover_policies(&mut self) {
+        let mut hide = false;
+        let mut freeze = false;
+        for (data, _) in self.layers[self.current_layer].states.iter_mut().rev() {
+            if data.hidden != hide {
+                data.hidden = hide;
+                if let Some(node) = data.rootnode {
+                    self.backend.set_visible(node, !hide);
+                }
+            }
+
+            if data.frozen != freeze {
+                data.frozen = freeze;
+                if let Some(node) = data.rootnode {
+                    self.backend.set_paused(node, freeze);
+                }
+            }
+
+            hide = hide || data.cover_policy.hides();
+            freeze = freeze || data.cover_policy.freezes();
+        }
+    }
+
+    /// Swaps the topmost state with the passed in state.
+    /// This is equivelent to calling pop then push.
+    pub fn switch(&mut self, state: Box<dyn State>) {
+        self.pop();
+        self.push(state);
+    }
+
+    /// Moves or clones entities from the world of the state at stack index from to the world of the state at stack index to,
+    /// then calls the on_transfer_in method of the state at to. Returns the new entities, this is empty if either index is not in the stack.
+    ///
+    /// See EntityTransfer for which components get carried over.
+    pub fn transfer(&mut self, from: usize, to: usize, transfer: EntityTransfer) -> Vec<Entity> {
+        let len = self.layers[self.current_layer].states.len();
+        if from == to || from >= len || to >= len {
+            return vec![];
+        }
+
+        let (src, dst) = if from < to {
+            let (below, above) = self.layers[self.current_layer].states.split_at_mut(to);
+            (&mut below[from].0, &mut above[0])
+        } else {
+            let (below, above) = self.layers[self.current_layer].states.split_at_mut(from);
+            (&mut above[0].0, &mut below[to])
+        };
+
+        let entities = transfer.run(&mut *self.backend, src, &mut dst.0);
+        let (data, state) = dst;
+        data.scoped(&mut self.resources, |data, resources| state.on_transfer_in(data, resources, &entities));
+        entities
+    }
+
+    /// Pushes the passed in state, transfers the entities into it from the state below it and then tears the state below it down.
+    /// Unlike switch() the old state gets on_cover called before on_pop, and the new state gets on_push before on_transfer_in.
+    pub fn switch_carrying(&mut self, state: Box<dyn State>, transfer: EntityTransfer) {
+        if self.layers[self.current_layer].states.is_empty() {
+            self.push(state);
+            return;
+        }
+
+        self.push(state);
+        let top = self.layers[self.current_layer].states.len() - 1;
+        self.transfer(top - 1, top, transfer);
+        self.teardown_at(top - 1);
+        self.stack_changed();
+    }
+
+    /// Replaces the entire stack with the passed in state.
+    /// This is equivelent to calling new_stack() with only one state.
+    pub fn replace(&mut self, state: Box<dyn State>) {
+        self.new_stack(vec![state]);
+    }
+
+    /// Pops all the states off the stack with Teardown::Silent, then calls push() for each of the states passed in.
+    /// See new_stack_with() to let the old states uncover as they are popped.
+    pub fn new_stack(&mut self, states: Vec<Box<dyn State>>) {
+        self.new_stack_with(states, Teardown::Silent);
+    }
+
+    /// Pops all the states off the stack from the top down calling on_pop and freeing their nodes,
+    /// with Teardown::Graceful each state that ends up at the top also gets on_uncover called before it is popped.
+    /// Then calls push() for each of the states passed in
+    pub fn new_stack_with(&mut self, states: Vec<Box<dyn State>>, teardown: Teardown) {
+        while self.teardown_top() {
+            if teardown == Teardown::Graceful {
+                if let Some((data, state)) = self.layers[self.current_layer].states.last_mut() {
+                    data.scoped(&mut self.resources, |data, resources| state.on_uncover(data, resources));
+                }
+            }
+        }
+
+        for state in states.into_iter() {
+            self.push(state);
         }
+        self.stack_changed();
+    }
+
+    /// Runs a Trans on the named layer instead of the current one, if there is no layer with that name it is rejected
+    pub fn on_layer(&mut self, layer: &str, trans: Trans) {
+        self.run_state_trans(Trans::OnLayer(layer.into(), Box::new(trans)));
+    }
+
+    /// Executes a set of Trans in sequential order, stopping at the first one that is rejected.
+    /// The Trans before the rejected one are not undone, RejectedTrans::applied says how many of them ran
+    pub fn sequence(&mut self, sequence: Vec<Trans>) {
+        self.run_state_trans(Trans::Sequence(sequence));
     }
 
-    /// Calls the on_cover method of the state at the top of the stack,
-    /// then calls the on_push method of the passed in state,
-    /// then pushes the passed in state onto the state stack.
-    pub fn push(&mut self, mut state: Box<dyn State>) {
-        // Send on_cover event to current top of stack if there is one
-        let state_len = self.states.len();
-        if state_len >= 1 {
-            let state = self.states.get_mut(state_len - 1).unwrap();
-            state.1.on_cover(&mut state.0, &mut self.resources);
-        }
+    /// Pushes the loading state then starts loading the models in the background.
+    /// Once every model has loaded they are inserted into Models\<T>, the loading state is popped and then the passed in Trans is run.
+    /// 
+    /// If a model fails to load the on_load_error method of the loading state is called and the Trans it returns is run instead of the passed in Trans,
+    /// if the loading state is popped before loading finishes the load is cancelled.
+    /// 
+    /// Only one load can run at a time, if another load is still running nothing is pushed and the Trans::LoadThen shows up in TransResource::rejected().
+    pub fn load_then(&mut self, loading: Box<dyn State>, models: Vec<ModelLoad<T>>, then: Trans) {
+        self.run_state_trans(Trans::LoadThen(loading, models.into(), Box::new(then)));
+    }
 
-        // Create new world
-        let world = self.universe.create_world();
-        let mut data = StateData::new(world);
+    fn begin_load(&mut self, loading: Box<dyn State>, models: Vec<ModelLoad<T>>, then: Trans) {
+        self.push(loading);
+        self.loading = Some(ActiveLoad::new(self.current_layer, self.layers[self.current_layer].states.len() - 1, models, then));
+        self.resources.insert(LoadingProgress::default());
+        self.poll_loading();
+    }
+
+    /// Polls the background load until it finishes or the frame's loading budget runs out
+    pub(crate) fn poll_loading(&mut self) {
+        const LOAD_BUDGET: std::time::Duration = std::time::Duration::from_millis(8);
+
+        let mut load = match self.loading.take() {
+            Some(load) => load,
+            None => return,
+        };
+
+        // The loading state was popped so cancel the load
+        if self.layers[load.layer].states.len() <= load.state_index {
+            return;
+        }
 
-        unsafe {
-            // Create rootnode
-            let mut rootnode = Node::new();
-            let name = state.get_name(&mut data, &mut self.resources);
-            rootnode.set_name(format!("State{}: {}", state_len, name).into());
-            self.owner.add_child(Some(rootnode), true);
-            data.rootnode = Some(rootnode);
-
-            // Create containernode
-            let mut containernode = Node::new();
-            containernode.set_name("RenderablesContainer".into());
-            data.rootnode.unwrap().add_child(Some(containernode), true);
-            data.containernode = Some(containernode);
-
-            // Create statenode
-            if let Some(index) = state.is_node(&mut data, &mut self.resources) {
-                let models = self.resources.get::<Models<T>>().unwrap();
-                if let Some(packed_scene) = (*models).scene_from_index(index) {
-                    let instance = packed_scene.instance(0).unwrap().cast::<Node>().unwrap();
-                    rootnode.add_child(Some(instance), true);
-                    data.statenode = Some(instance);
+        let start = std::time::Instant::now();
+        let mut error = None;
+        while !load.is_finished() && start.elapsed() < LOAD_BUDGET {
+            // Start loading the next model
+            if load.current.is_none() {
+                let model = load.pending.pop_front().unwrap();
+                match self.backend.start_load(&model.path) {
+                    Ok(handle) => load.current = Some((model, handle)),
+                    Err(message) => {
+                        error = Some(LoadError { path: model.path, message });
+                        break;
+                    },
                 }
             }
+
+            let (model, handle) = load.current.take().unwrap();
+            match self.backend.poll_load(handle) {
+                LoadStatus::Loading(progress) => {
+                    load.current_progress = progress;
+                    load.current = Some((model, handle));
+                },
+                LoadStatus::Loaded(scene) => {
+                    load.current_progress = 0f32;
+                    load.loaded.push((model, scene));
+                },
+                LoadStatus::Failed(message) => {
+                    error = Some(LoadError { path: model.path, message });
+                    break;
+                },
+            }
         }
 
-        // Actually push state onto the stack
-        state.on_push(&mut data, &mut self.resources);
-        self.states.push((data, state));
-    }
+        let mut progress = load.progress();
 
-    /// Calls the on_pop method of the state at the top of the stack,
-    /// then calls the on_uncover method of the state below the state at the top of the stack,
-    /// then pops the state at the top of the stack.
-    pub fn pop(&mut self) {
-        let state_len = self.states.len();
+        // Send errors to the loading state
+        if let Some(error) = error {
+            progress.errors.push(error.clone());
+            self.resources.insert(progress);
+            self.fail_load(load.layer, load.state_index, error);
+            return;
+        }
+        self.resources.insert(progress);
 
-        if let Some(state) = self.states.last_mut() {            
-            state.1.on_pop(&mut state.0, &mut self.resources);
+        if !load.is_finished() {
+            self.loading = Some(load);
+            return;
+        }
 
-            if let Some(node) = state.0.rootnode {
-                unsafe { node.free(); }
-            }
+        // Insert the loaded models
+        let inserted = match self.resources.get_mut::<Models<T>>() {
+            Some(mut models) => {
+                for (model, scene) in load.loaded.into_iter() {
+                    models.insert_scene(model.alias, model.t_key, scene, model.template);
+                }
+                true
+            },
+            None => false,
+        };
 
-            if state_len >= 2 {
-                let state = self.states.get_mut(state_len - 2).unwrap();
-                state.1.on_uncover(&mut state.0, &mut self.resources);
+        if !inserted {
+            let error = LoadError { path: String::new(), message: "There is no Models<T> resource to insert the loaded models into".into() };
+            if let Some(mut progress) = self.resources.get_mut::<LoadingProgress>() {
+                progress.errors.push(error.clone());
             }
-            self.states.pop();
+            self.fail_load(load.layer, load.state_index, error);
+            return;
         }
-    }
 
-    /// Swaps the topmost state with the passed in state.
-    /// This is equivelent to calling pop then push.
-    pub fn switch(&mut self, state: Box<dyn State>) {
-        self.pop();
-        self.push(state);
+        // Pop the loading state and anything that was pushed over it then run the Trans
+        let (layer, state_index, then) = (load.layer, load.state_index, load.then);
+        self.with_layer(layer, |engine| {
+            engine.pop_n(engine.layers[layer].states.len() - state_index);
+            engine.run_state_trans(then);
+        });
     }
 
-    /// Replaces the entire stack with the passed in state.
-    /// This is equivelent to calling new_stack() with only one state.
-    pub fn replace(&mut self, state: Box<dyn State>) {
-        self.new_stack(vec![state]);
+    /// Passes the error to the on_load_error method of the loading state and runs the Trans it returns on the loading state's layer
+    fn fail_load(&mut self, layer: usize, state_index: usize, error: LoadError) {
+        self.with_layer(layer, |engine| {
+            let (data, state) = engine.layers[layer].states.get_mut(state_index).unwrap();
+            let trans = data.scoped(&mut engine.resources, |data, resources| state.on_load_error(data, resources, &error));
+            engine.run_state_trans(trans);
+        });
     }
 
-    /// Pops all the states off the stack without calling on_uncover.
-    /// This means that calling new_stack is NOT the same as popping all of the states as new_stack does not allow any of the states to run code when they are popped.
-    /// Then calls push() for each of the states passed in
-    pub fn new_stack(&mut self, states: Vec<Box<dyn State>>) {
-        while let Some(mut popped) = self.states.pop() {
-            popped.1.on_pop(&mut popped.0, &mut self.resources);
+    /// Shuts down the engine.
+    /// From the top layer down and from the top of each stack down each state has on_quit then on_pop called on it and then has its nodes freed,
+    /// once every stack is empty the nodes of the global world are freed and the backend is asked to quit (the GodotBackend quits the SceneTree).
+    /// Calling this again after the engine has quit does nothing.
+    pub fn quit(&mut self) {
+        if self.finished {
+            return;
         }
 
-        for state in states.into_iter() {
-            self.push(state);
+        for layer in (0..self.layers.len()).rev() {
+            self.with_layer(layer, |engine| {
+                while let Some((data, state)) = engine.layers[engine.current_layer].states.last_mut() {
+                    data.scoped(&mut engine.resources, |data, resources| state.on_quit(data, resources));
+                    engine.teardown_top();
+                }
+                engine.stack_changed();
+            });
         }
+
+        // Close the file of the recording started by record_to()
+        self.recording = None;
+
+        // Free the global world's nodes, its world is kept in the resources in case anything reads from it after quitting
+        self.backend.free_node(self.global.rootnode);
+        self.global.node_lookup.clear();
+
+        self.finished = true;
+        self.backend.quit();
     }
+}
 
-    /// Executes a set of Trans in sequential order
-    pub fn sequence(&mut self, sequence: Vec<Trans>) {
-        for trans in sequence.into_iter() {
-            self.run_state_trans(trans);
-        }
+/// Frees the rootnode of a state which also frees its statenode and all of its renderables
+pub(crate) fn free_state_nodes(backend: &mut dyn SceneBackend, data: &mut StateData) {
+    if let Some(node) = data.rootnode.take() {
+        backend.free_node(node);
     }
+    data.containernode = None;
+    data.statenode = None;
+    data.node_lookup.clear();
 }
 
 pub(crate) fn get_animator<T>(node: Node) -> Option<T> 
@@ -242,77 +1323,149 @@ pub(crate) fn get_animator<T>(node: Node) -> Option<T>
     None
 }
 
-pub(crate) fn sync_state<T>(resources: &mut Resources, state: &mut (StateData, Box<(dyn State)>)) 
+/// Instances the model at index as a child of parent if the index exists in Models\<T>
+pub(crate) fn instance_model<T>(backend: &mut dyn SceneBackend, models: &Models<T>, parent: SceneNode, name: &str, index: usize) -> Option<SceneNode>
+    where
+    T: Eq + std::hash::Hash + 'static {
+    
+    if models.data_from_index(index).is_none() {
+        return None;
+    }
+    backend.instance_scene(parent, name, models.scene_from_index(index), index)
+}
+
+pub(crate) fn sync_state<T>(resources: &mut Resources, backend: &mut dyn SceneBackend, state: &mut (StateData, Box<(dyn State)>)) 
     where
     T: Eq + std::hash::Hash + 'static {
     
     let models = resources.get::<Models<T>>().unwrap();
+    let data = &mut state.0;
+    sync_world(backend, &models, &mut data.world, data.containernode.unwrap(), &data.receiver, &mut data.node_lookup);
+}
+
+pub(crate) fn sync_global<T>(resources: &Resources, backend: &mut dyn SceneBackend, global: &mut GlobalLayer) 
+    where
+    T: Eq + std::hash::Hash + 'static {
+    
+    let models = resources.get::<Models<T>>().unwrap();
+    if let Some(mut global_world) = resources.get_mut::<GlobalWorld>() {
+        sync_world(backend, &models, &mut global_world.world, global.containernode, &global.receiver, &mut global.node_lookup);
+    }
+}
+
+/// Syncs the Renderables in a world to godot, instancing them under the container node
+pub(crate) fn sync_world<T>(
+    backend: &mut dyn SceneBackend, 
+    models: &Models<T>, 
+    world: &mut LWorld, 
+    containernode: SceneNode, 
+    receiver: &crossbeam_channel::Receiver<legion::event::Event>, 
+    node_lookup: &mut HashMap<Entity, SceneNode>) 
+    where
+    T: Eq + std::hash::Hash + 'static {
 
     // Sync renderable tree
     let query = <Write<Renderable>>::query()
         .filter(changed::<Renderable>());
-    for mut renderable in query.iter_mut(&mut state.0.world) {
-        sync_renderable_recursive(&mut state.0.containernode.unwrap(), &mut renderable, &models);
+    for (entity, mut renderable) in query.iter_entities_mut(world) {
+        sync_renderable_recursive(backend, containernode, &mut renderable, models);
+        node_lookup.insert(entity, renderable.container_node.unwrap());
     }
 
+    sync_removals(backend, world, Some(containernode), receiver, node_lookup);
+    sync_hierarchy(backend, world, containernode, node_lookup);
+
+    // The schedules may have moved entities since the start of the frame
+    propagate_transforms(world);
+    let unnested = unnested_children(world);
+
     // Sync entity position to renderable tree root
     let query = <(Read<Position>, Write<Renderable>)>::query()
         .filter(changed::<Position>());
-    for (pos, mut renderable) in query.iter_mut(&mut state.0.world) {
-        sync_transform_to_node(&pos, renderable.container_node.unwrap());
+    for (entity, (pos, renderable)) in query.iter_entities_mut(world) {
+        if !unnested.contains_key(&entity) {
+            backend.set_transform(renderable.container_node.unwrap(), &pos);
+        }
+    }
+
+    let query = <(Read<Position3D>, Write<Renderable>)>::query()
+        .filter(changed::<Position3D>());
+    for (entity, (pos, renderable)) in query.iter_entities_mut(world) {
+        if !unnested.contains_key(&entity) {
+            backend.set_transform_3d(renderable.container_node.unwrap(), &pos);
+        }
     }
 
-    // Free godot nodes whos respective entities have been disposed
-    for event in state.0.receiver.try_iter() {
+    sync_unnested(backend, world, &unnested);
+}
+
+/// Frees the godot nodes of entities that have been deleted or had their Renderable removed,
+/// this drains the world's events so it is run for every state each frame even if the state isn't synced.
+/// Children of deleted entities are deleted here as well
+pub(crate) fn sync_removals(
+    backend: &mut dyn SceneBackend, 
+    world: &mut LWorld, 
+    containernode: Option<SceneNode>,
+    receiver: &crossbeam_channel::Receiver<legion::event::Event>, 
+    node_lookup: &mut HashMap<Entity, SceneNode>) {
+
+    despawn_orphans(world);
+
+    let mut removed = vec![];
+    for event in receiver.try_iter() {
         use legion::event::Event::*;
         match event {
             EntityRemoved(e, _) => {
-                if let None = state.0.world.get_component::<Renderable>(e) {
-                    if let Some(node) = state.0.node_lookup.get_mut(&e) {
-                        unsafe { node.free(); }
-                        state.0.node_lookup.remove(&e);
+                if let None = world.get_component::<Renderable>(e) {
+                    if let Some(node) = node_lookup.remove(&e) {
+                        removed.push((e, node));
                     }
                 }
             },
             EntityInserted(e, _) => {
-                if let Some(renderable) = state.0.world.get_component::<Renderable>(e) {
-                    if let Some(_) = state.0.world.get_component::<Position>(e) {
-                        state.0.node_lookup.insert(e, renderable.container_node.unwrap());
+                if let Some(renderable) = world.get_component::<Renderable>(e) {
+                    if let Some(node) = renderable.container_node {
+                        node_lookup.insert(e, node);
                     }
                 } 
             }
             _ => { }
         }
     }
+
+    if removed.is_empty() {
+        return;
+    }
+    if let Some(containernode) = containernode {
+        unnest_removed(backend, world, containernode, &removed);
+    }
+    for (_, node) in removed {
+        backend.free_node(node);
+    }
 }
 
-pub(crate) fn sync_renderable_recursive<T>(parent: &mut Node, renderable: &mut Renderable, models: &Models<T>)
+pub(crate) fn sync_renderable_recursive<T>(backend: &mut dyn SceneBackend, parent: SceneNode, renderable: &mut Renderable, models: &Models<T>)
     where
     T: Eq + std::hash::Hash + 'static {
+    // Renderables placed in 3D need Spatial containers so that their transforms carry through to their children
+    let is_3d = renderable.transform_3d.is_some() || renderable.renderable_id.map_or(false, |id| models.is_spatial(id));
+    let container_kind = if is_3d { NodeKind::Spatial } else { NodeKind::Node2D };
+
     // Create container node
     if let None = renderable.container_node {
-        unsafe {
-            let mut node = Node2D::new().cast::<Node>().unwrap();
-            node.set_name("Renderable".into());
-            parent.add_child(Some(node), true);
-            renderable.container_node = Some(node);
-        }
+        renderable.container_node = Some(backend.create_node(parent, "Renderable", container_kind));
     }
     
     // Create children container
     if let None = renderable.children_node {
-        unsafe {
-            let mut node = Node2D::new().cast::<Node>().unwrap();
-            node.set_name("Children".into());
-            renderable.container_node.unwrap().add_child(Some(node), true);
-            renderable.children_node = Some(node);
-        }
+        renderable.children_node = Some(backend.create_node(renderable.container_node.unwrap(), "Children", container_kind));
     }
 
     // Free renderable node if dirty
     if let (Some(spatial), Some(node)) = (renderable.spatial, renderable.renderable_node) {
         if spatial.is_id_dirty(&renderable) {
-            unsafe { node.free(); }
+            backend.free_node(node);
+            renderable.renderable_node = None;
         }
     }
 
@@ -321,62 +1474,50 @@ pub(crate) fn sync_renderable_recursive<T>(parent: &mut Node, renderable: &mut R
         renderable.renderable_id.is_some() && 
         renderable.template.is_some() 
     {
-        unsafe {
-            let scene = models.scene_from_index(renderable.renderable_id.unwrap()).unwrap();
-            let mut instance = scene.instance(0).unwrap().cast::<Node>().unwrap();
-            instance.set_name("Node".into());
-            renderable.container_node.unwrap().add_child(Some(instance), true);
-            renderable.renderable_node = Some(instance);
-            if let Some(_) = renderable.spatial {
-                renderable.spatial = Some(GDSpatial { prev_id: renderable.renderable_id, prev_pos: None });
-            }
+        renderable.renderable_node = instance_model(backend, models, renderable.container_node.unwrap(), "Node", renderable.renderable_id.unwrap());
+        if let Some(_) = renderable.spatial {
+            renderable.spatial = Some(GDSpatial { prev_id: renderable.renderable_id, prev_pos: None, prev_pos_3d: None });
         }
     }
 
     // Update visibility
-    unsafe {
-        if let Some(_) = renderable.spatial {
-            renderable.container_node.unwrap().cast::<Node2D>().unwrap().set_visible(true);
-        } else {
-            renderable.container_node.unwrap().cast::<Node2D>().unwrap().set_visible(false);
-            return;
-        }
+    if let Some(_) = renderable.spatial {
+        backend.set_visible(renderable.container_node.unwrap(), true);
+    } else {
+        backend.set_visible(renderable.container_node.unwrap(), false);
+        return;
     }
 
     // If our current renderable actually has stuff to render
     if let Some(node) = renderable.renderable_node {
         // Sync position to childrens parent node and to renderable node
         if renderable.spatial.unwrap().is_pos_dirty(&renderable) {
-            sync_transform_to_node(&renderable.transform, node);
-            if let Some(node) = renderable.children_node {
-                sync_transform_to_node2d(&renderable.transform, unsafe{node.cast::<Node2D>().unwrap()});
+            let mut nodes = vec![node];
+            nodes.extend(renderable.children_node);
+            for node in nodes {
+                if is_3d {
+                    backend.set_transform_3d(node, &renderable.transform_3d.unwrap_or_else(|| Position3D::from(renderable.transform)));
+                } else {
+                    backend.set_transform(node, &renderable.transform);
+                }
             }
-            renderable.spatial = Some(GDSpatial { prev_id: renderable.renderable_id, prev_pos: Some(renderable.transform)});
+            renderable.spatial = Some(GDSpatial { 
+                prev_id: renderable.renderable_id, 
+                prev_pos: Some(renderable.transform), 
+                prev_pos_3d: renderable.transform_3d,
+            });
         }
 
         // Animations
         match renderable.template.unwrap() {
             Template::ASprite(state) => {
-                if let Some(mut sprite) = get_animator::<AnimatedSprite>(node) {
-                    // Update node from state
-                    unsafe {
-                        sprite._set_playing(state.playing);
-                        let gd_string = GodotString::from(state.animation);
-                        sprite.play(gd_string, false);
-                        sprite.set_flip_h(state.flip_h);
-                        sprite.set_flip_v(state.flip_v);
-                    }
-                }
+                backend.set_anim_sprite(node, &state);
             },
             Template::APlayer(_state) => {
-                if let Some(_sprite) = get_animator::<AnimationTree>(node) {
-                    // Update node from state
-                }
+                // Update node from state
             },
             Template::ATree(_state) => {
-                if let Some(_sprite) = get_animator::<AnimationTree>(node) {
-                    // Update node from state
-                }
+                // Update node from state
             },
             _ => {}
         }
@@ -385,7 +1526,7 @@ pub(crate) fn sync_renderable_recursive<T>(parent: &mut Node, renderable: &mut R
     // Delete orphans
     while let Some(orphan) = renderable.orphans.pop() {
         if let Some(orphan) = orphan {
-            unsafe { orphan.free(); }
+            backend.free_node(orphan);
         }
     }
 
@@ -395,61 +1536,51 @@ pub(crate) fn sync_renderable_recursive<T>(parent: &mut Node, renderable: &mut R
         if child.container_node.is_none() {
             // Try find a parent node
             for node in renderable.children_containers.iter() {
-                unsafe { 
-                    if node.get_child_count() < 500 {
-                        res = Some(*node);
-                        break;
-                    }
+                if backend.get_child_count(*node) < 500 {
+                    res = Some(*node);
+                    break;
                 }
             }
     
             // Make parent node if we couldnt find one
             if let None = res {
-                unsafe {
-                    res = Some(Node2D::new().cast::<Node>().unwrap());
-                    res.unwrap().set_name("NodeBatch".into());
-                    renderable.children_node.unwrap().add_child(res, true);
-                }
+                res = Some(backend.create_node(renderable.children_node.unwrap(), "NodeBatch", container_kind));
                 renderable.children_containers.push(res.unwrap());
             }
         } else {
-            unsafe {
-                res = Some(child.container_node.unwrap().get_parent().unwrap());
-            }
+            res = backend.get_parent(child.container_node.unwrap());
         }
 
-        //sync_renderable_recursive(&mut renderable.children_node.unwrap(), child, models)
-        sync_renderable_recursive(&mut res.unwrap(), child, models);
+        sync_renderable_recursive(backend, res.unwrap(), child, models);
     }
 }
 
 pub(crate) fn sync_transform_to_node(pos: &Position, node: Node) {
     if let Some(spatial) = unsafe { node.cast::<Spatial>() } {
-        sync_transform_to_spatial(pos, spatial);
+        sync_transform_to_spatial(&Position3D::from(*pos), spatial);
     } else if let Some(node2d) = unsafe { node.cast::<Node2D>() } {
         sync_transform_to_node2d(pos, node2d);
     }
 }
 
-pub(crate) fn sync_transform_to_spatial(pos: &Position, mut spatial: Spatial) {
-    // Position
-    let mut transform = unsafe { spatial.get_translation() };
-    transform.x = pos.x as f32;
-    transform.z = pos.y as f32;
-    unsafe { spatial.set_translation(transform) };
+pub(crate) fn sync_transform_3d_to_node(pos: &Position3D, node: Node) {
+    if let Some(spatial) = unsafe { node.cast::<Spatial>() } {
+        sync_transform_to_spatial(pos, spatial);
+    }
+}
 
-    // Rotation
-    let mut rotation = unsafe { spatial.get_rotation() };
-    rotation.y = pos.rotation.get();
+pub(crate) fn sync_transform_to_spatial(pos: &Position3D, mut spatial: Spatial) {
+    unsafe { spatial.set_transform(pos.to_transform()) };
 }
 
 pub(crate) fn sync_transform_to_node2d(pos: &Position, mut node2d: Node2D) {
-    let mut position = unsafe { node2d.get_position() };
-    position.x = pos.x as f32;
-    position.y = pos.y as f32;
-    unsafe { node2d.set_position(position) };
-    unsafe { node2d.set_rotation(pos.rotation.get() as f64) };
+    unsafe {
+        node2d.set_transform(pos.to_transform2d());
+        node2d.set_z_index(pos.z_index);
+        node2d.set_z_as_relative(pos.z_as_relative);
+    }
 }
+
 /// Takes a path to a scene prepends res://scenes/ and appends .tscn then attempts to load the scene
 /// 
 /// this path is CAPS SENSITIVE it is EXTREMELY important that your scenes folder is ALL lowercase and your specified path is correctly cased or else it WILL NOT WORK ON LINUX
@@ -485,4 +1616,562 @@ pub fn load_scene(scene_path: &str) -> PackedScene {
         Err(e) => panic!(e),
         Ok(template) => template,
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::harness::tests::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn rejected(harness: &mut TestHarness<i32>) -> Vec<RejectedTrans> {
+        harness.resources().get::<TransResource>().unwrap().rejected().to_vec()
+    }
+
+    #[test]
+    fn every_transition_sent_in_a_frame_is_run() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let (b, c) = (LogState::new("B", &log), LogState::new("C", &log));
+        send(&mut harness, move || Trans::Push(b));
+        send(&mut harness, move || Trans::Push(c));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn higher_priority_transitions_run_first() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let (b, c, d) = (LogState::new("B", &log), LogState::new("C", &log), LogState::new("D", &log));
+        {
+            let sender = harness.resources().get::<TransResource>().unwrap();
+            sender.send(move || Trans::Push(b)).unwrap();
+            sender.send_with_priority(1, move || Trans::Push(c)).unwrap();
+            sender.send(move || Trans::Push(d)).unwrap();
+        }
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "C", "B", "D"]);
+    }
+
+    #[test]
+    fn transitions_that_cant_run_are_rejected_until_the_next_frame() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        send(&mut harness, || Trans::Pop);
+        send(&mut harness, || Trans::Pop);
+        harness.step();
+        assert_eq!(harness.engine().depth(), 0);
+        assert_eq!(rejected(&mut harness), vec![RejectedTrans {
+            layer: "game".into(),
+            trans: "Pop".into(),
+            error: TransError::EmptyStack,
+            applied: 0,
+        }]);
+
+        harness.step();
+        assert!(rejected(&mut harness).is_empty());
+    }
+
+    #[test]
+    fn a_sequence_keeps_the_transitions_before_the_rejected_one() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let pushed = LogState::new("B", &log);
+        send(&mut harness, move || Trans::Sequence(vec![Trans::Pop, Trans::Pop, Trans::Push(pushed)]));
+        harness.step();
+        assert_eq!(harness.engine().depth(), 0);
+
+        let rejected = rejected(&mut harness);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].error, TransError::EmptyStack);
+        assert_eq!(rejected[0].applied, 1);
+    }
+
+    #[test]
+    fn quitting_calls_on_quit_then_on_pop_from_the_top_down() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+        take(&log);
+
+        send(&mut harness, || Trans::Quit);
+        harness.step();
+        assert!(harness.is_finished());
+        assert!(harness.tree().has_quit());
+        assert_eq!(harness.engine().depth(), 0);
+        assert_eq!(take(&log), vec!["B quit", "B pop", "A quit", "A pop"]);
+
+        harness.engine_mut().quit();
+        harness.step();
+        assert!(take(&log).is_empty());
+    }
+
+    #[test]
+    fn transitions_after_a_quit_are_not_run() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let pushed = LogState::new("B", &log);
+        send(&mut harness, || Trans::Quit);
+        send(&mut harness, move || Trans::Push(pushed));
+        harness.step();
+        assert_eq!(harness.engine().depth(), 0);
+        assert!(!take(&log).contains(&"B push".to_string()));
+    }
+
+    #[test]
+    fn quit_requests_go_to_the_top_state() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log))
+            .push(LogState::new("B", &log).answering_quit_with(|| Trans::None));
+        take(&log);
+
+        harness.request_quit();
+        assert!(!harness.is_finished());
+        assert_eq!(take(&log), vec!["B quit request"]);
+    }
+
+    #[test]
+    fn a_quit_request_can_push_a_confirmation_instead_of_quitting() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log).answering_quit_with(|| Trans::Push(LogState::new("Confirm", &Log::default()))));
+
+        harness.request_quit();
+        assert!(!harness.is_finished());
+        assert_eq!(harness.engine().state_names(), vec!["A", "Confirm"]);
+
+        harness.request_quit();
+        assert!(harness.is_finished());
+    }
+
+    #[test]
+    fn a_quit_request_with_no_states_quits_straight_away() {
+        let (mut harness, _) = harness();
+        harness.request_quit();
+        assert!(harness.is_finished());
+        assert!(harness.tree().has_quit());
+    }
+
+    /// Counts its shadow updates
+    struct ShadowCounter(Rc<Cell<u32>>);
+
+    impl State for ShadowCounter {
+        fn shadow_update(&mut self, _data: &mut StateData, _resources: &mut Resources) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn hide_and_freeze_hides_and_pauses_the_covered_states_until_uncovered() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("Menu", &log).covering(CoverPolicy::HideAndFreeze));
+
+        let covered = harness.tree().find("Layer: game/State0: A").unwrap();
+        assert!(harness.state_data(0).is_hidden() && harness.state_data(0).is_frozen());
+        assert!(!harness.tree().is_visible_in_tree(covered));
+        assert!(harness.tree().is_paused_in_tree(covered));
+        assert!(!harness.state_data(1).is_hidden());
+
+        send(&mut harness, || Trans::Pop);
+        harness.step();
+        assert!(!harness.state_data(0).is_hidden() && !harness.state_data(0).is_frozen());
+        assert!(harness.tree().is_visible_in_tree(covered));
+        assert!(!harness.tree().is_paused_in_tree(covered));
+    }
+
+    #[test]
+    fn only_unfrozen_covered_states_get_shadow_updates() {
+        for (policy, hidden, shadow_updates) in vec![
+            (CoverPolicy::KeepRunning, false, 2),
+            (CoverPolicy::Hide, true, 2),
+            (CoverPolicy::Freeze, false, 0),
+            (CoverPolicy::HideAndFreeze, true, 0),
+        ] {
+            let (mut harness, log) = harness();
+            let counter = Rc::new(Cell::new(0));
+            harness.push(Box::new(ShadowCounter(counter.clone()))).push(LogState::new("B", &log).covering(policy));
+            harness.step_n(2);
+
+            assert_eq!(harness.state_data(0).is_hidden(), hidden);
+            assert_eq!(counter.get(), shadow_updates);
+        }
+    }
+
+    /// Counts how often it gets uncovered
+    struct UncoverCounter(Rc<Cell<u32>>);
+
+    impl State for UncoverCounter {
+        fn on_uncover(&mut self, _data: &mut StateData, _resources: &mut Resources) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn pop_with_passes_the_result_to_the_state_below() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+        take(&log);
+
+        send(&mut harness, || Trans::PopWith(Box::new(5i32)));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(take(&log), vec!["B pop", "A uncover with 5"]);
+    }
+
+    #[test]
+    fn states_that_ignore_results_are_uncovered_as_usual() {
+        let (mut harness, log) = harness();
+        let uncovers = Rc::new(Cell::new(0));
+        harness.push(Box::new(UncoverCounter(uncovers.clone()))).push(LogState::new("B", &log));
+
+        send(&mut harness, || Trans::PopWith(Box::new(true)));
+        harness.step();
+        assert_eq!(uncovers.get(), 1);
+    }
+
+    #[test]
+    fn pop_with_on_an_empty_stack_is_rejected() {
+        let (mut harness, _) = harness();
+        send(&mut harness, || Trans::PopWith(Box::new(5i32)));
+        harness.step();
+        assert_eq!(rejected(&mut harness)[0].error, TransError::EmptyStack);
+    }
+
+    #[test]
+    fn pop_n_only_uncovers_the_new_top() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log)).push(LogState::new("C", &log));
+        take(&log);
+
+        send(&mut harness, || Trans::PopN(2));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(take(&log), vec!["C pop", "B pop", "A uncover"]);
+    }
+
+    #[test]
+    fn pop_to_stops_at_the_matching_state() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log)).push(LogState::new("C", &log));
+        take(&log);
+
+        send(&mut harness, || Trans::PopTo("A".into()));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(take(&log), vec!["C pop", "B pop", "A uncover"]);
+
+        harness.push(LogState::new("B", &log)).push(LogState::new("C", &log));
+        send(&mut harness, || Trans::PopUntil(Box::new(|name| name == "B")));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn stack_navigation_that_cant_run_is_rejected() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        send(&mut harness, || Trans::PopN(3));
+        send(&mut harness, || Trans::PopTo("Missing".into()));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+
+        let errors: Vec<TransError> = rejected(&mut harness).into_iter().map(|rejected| rejected.error).collect();
+        assert_eq!(errors, vec![TransError::NotEnoughStates { requested: 3, depth: 1 }, TransError::NoMatchingState]);
+    }
+
+    #[test]
+    fn the_stack_resource_follows_the_stack() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+
+        {
+            let stack = harness.resources().get::<StackResource>().unwrap();
+            assert_eq!(stack.depth(), 2);
+            assert_eq!(stack.names(), &["A".to_string(), "B".to_string()][..]);
+            assert_eq!(stack.top(), Some("B"));
+            assert!(stack.contains("A") && !stack.contains("C"));
+        }
+
+        send(&mut harness, || Trans::Pop);
+        harness.step();
+        assert_eq!(harness.resources().get::<StackResource>().unwrap().top(), Some("A"));
+    }
+
+    #[test]
+    fn replace_tears_down_the_stack_silently() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+        take(&log);
+
+        let replacement = LogState::new("C", &log);
+        send(&mut harness, move || Trans::Replace(replacement));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["C"]);
+        assert_eq!(take(&log), vec!["B pop", "A pop", "C push"]);
+        assert!(harness.tree().find("Layer: game/State0: A").is_none());
+        assert!(harness.tree().find("Layer: game/State1: B").is_none());
+    }
+
+    #[test]
+    fn graceful_teardown_uncovers_each_state_before_popping_it() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+        take(&log);
+
+        let stack: Vec<Box<dyn State>> = vec![LogState::new("C", &log), LogState::new("D", &log)];
+        send(&mut harness, move || Trans::NewStackWith(stack, Teardown::Graceful));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["C", "D"]);
+        assert_eq!(take(&log), vec!["B pop", "A uncover", "A pop", "C push", "C cover", "D push"]);
+    }
+
+    #[test]
+    fn switch_pops_the_top_state_before_pushing() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(LogState::new("B", &log));
+        take(&log);
+
+        let switched = LogState::new("C", &log);
+        send(&mut harness, move || Trans::Switch(switched));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "C"]);
+        assert_eq!(take(&log), vec!["B pop", "A uncover", "A cover", "C push"]);
+        assert!(harness.tree().find("Layer: game/State1: B").is_none());
+    }
+
+    fn square_harness() -> (TestHarness<i32>, Log) {
+        let mut models = Models::<i32>::default();
+        models.insert_headless(Some("Square"), None, Template::None);
+        (TestHarness::new(models), Log::default())
+    }
+
+    fn square() -> Renderable {
+        Renderable::new(Position::default(), 0, Template::None)
+    }
+
+    fn global_nodes(harness: &TestHarness<i32>) -> Vec<usize> {
+        let container = harness.engine().global_container().headless().unwrap();
+        harness.tree().get(container).unwrap().children.clone()
+    }
+
+    #[test]
+    fn the_global_world_is_synced_through_every_transition() {
+        let (mut harness, log) = square_harness();
+        harness.push(LogState::new("A", &log));
+        let entity = harness.resources().get_mut::<GlobalWorld>().unwrap().world.insert((), vec![(square(), Position::new(1f32, 2f32))])[0];
+        harness.step();
+
+        assert_eq!(harness.tree().find("Global/RenderablesContainer"), harness.engine().global_container().headless());
+        let nodes = global_nodes(&harness);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(harness.tree().get(nodes[0]).unwrap().transform, Some(Position::new(1f32, 2f32)));
+
+        let replacement = LogState::new("B", &log);
+        send(&mut harness, move || Trans::Replace(replacement));
+        harness.step();
+        assert_eq!(global_nodes(&harness), nodes);
+
+        harness.resources().get_mut::<GlobalWorld>().unwrap().world.delete(entity);
+        harness.step();
+        assert!(global_nodes(&harness).is_empty());
+        assert!(harness.tree().get(nodes[0]).is_none());
+    }
+
+    #[test]
+    fn quitting_frees_the_global_nodes_but_keeps_the_world() {
+        let (mut harness, log) = square_harness();
+        harness.push(LogState::new("A", &log));
+        harness.resources().get_mut::<GlobalWorld>().unwrap().world.insert((), vec![(square(), Position::default())]);
+        harness.step();
+        let nodes = global_nodes(&harness);
+
+        harness.request_quit();
+        assert!(harness.tree().find("Global").is_none());
+        assert!(harness.tree().get(nodes[0]).is_none());
+        assert_eq!(<Read<Renderable>>::query().iter(&harness.resources().get::<GlobalWorld>().unwrap().world).count(), 1);
+    }
+
+    /// A harness with A on the game layer and Hud on the ui layer above it, the ui layer is selected
+    fn layered_harness() -> (TestHarness<i32>, Log) {
+        let (mut harness, log) = square_harness();
+        harness.engine_mut().add_layer("ui");
+        harness.push(LogState::new("A", &log));
+        harness.engine_mut().select_layer("ui");
+        harness.push(LogState::new("Hud", &log));
+        take(&log);
+        (harness, log)
+    }
+
+    #[test]
+    fn every_layer_is_updated_from_the_top_layer_down() {
+        let (mut harness, log) = layered_harness();
+        harness.tap_action("jump");
+        assert_eq!(take(&log), vec!["Hud jump", "A jump"]);
+    }
+
+    #[test]
+    fn every_layer_is_synced() {
+        let (mut harness, _) = layered_harness();
+        harness.world_mut(0).insert((), vec![(square(), Position::default())]);
+        harness.engine_mut().select_layer("game");
+        harness.world_mut(0).insert((), vec![(square(), Position::default())]);
+        harness.step();
+
+        assert_eq!(harness.renderable_count(0), 1);
+        assert!(harness.tree().find("Layer: game/State0: A/RenderablesContainer").is_some());
+        harness.engine_mut().select_layer("ui");
+        assert_eq!(harness.renderable_count(0), 1);
+        assert!(harness.tree().find("Layer: ui/State0: Hud/RenderablesContainer").is_some());
+    }
+
+    #[test]
+    fn send_to_runs_the_trans_on_the_named_layer() {
+        let (mut harness, log) = layered_harness();
+        harness.engine_mut().select_layer("game");
+        harness.resources().get::<TransResource>().unwrap().send_to("ui", || Trans::Pop).unwrap();
+        harness.resources().get::<TransResource>().unwrap().send_to("nowhere", || Trans::Pop).unwrap();
+        harness.step();
+
+        assert_eq!(take(&log), vec!["Hud pop"]);
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(rejected(&mut harness)[0].error, TransError::UnknownLayer("nowhere".into()));
+
+        let stack = harness.resources().get::<StackResource>().unwrap();
+        assert_eq!(stack.layers(), vec!["game", "ui"]);
+        assert!(stack.layer_names("ui").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quitting_tears_down_the_top_layer_first() {
+        let (mut harness, log) = layered_harness();
+        harness.request_quit();
+        assert_eq!(take(&log), vec!["Hud quit request", "Hud quit", "Hud pop", "A quit", "A pop"]);
+    }
+
+    /// Is only synced while it is at the top of the stack
+    struct UnsyncedState;
+
+    impl State for UnsyncedState {
+        fn sync_when_covered(&mut self, _data: &mut StateData, _resources: &mut Resources) -> bool { false }
+    }
+
+    #[test]
+    fn covered_states_that_are_visible_are_synced() {
+        let (mut harness, log) = square_harness();
+        harness.push(LogState::new("A", &log));
+        let entity = harness.world_mut(0).insert((), vec![(square(), Position::default())])[0];
+        harness.push(LogState::new("B", &log));
+        harness.step();
+        assert_eq!(harness.renderable_count(0), 1);
+
+        harness.world_mut(0).get_component_mut::<Position>(entity).unwrap().x = 5f32;
+        harness.step();
+        let node = harness.world(0).get_component::<Renderable>(entity).unwrap().container_node.unwrap();
+        assert_eq!(harness.tree().get_node(node).unwrap().transform.unwrap().x, 5f32);
+    }
+
+    #[test]
+    fn unsynced_states_still_free_the_nodes_of_removed_entities() {
+        for covering in vec![CoverPolicy::Hide, CoverPolicy::KeepRunning] {
+            let (mut harness, log) = square_harness();
+            let first: Box<dyn State> = match covering {
+                CoverPolicy::Hide => LogState::new("A", &log),
+                _ => Box::new(UnsyncedState),
+            };
+            harness.push(first);
+            let entity = harness.world_mut(0).insert((), vec![(square(), Position::default())])[0];
+            harness.step();
+            let node = harness.world(0).get_component::<Renderable>(entity).unwrap().container_node.unwrap();
+
+            harness.push(LogState::new("B", &log).covering(covering));
+            harness.world_mut(0).insert((), vec![(square(), Position::default())]);
+            harness.step();
+            assert_eq!(harness.renderable_count(0), 1);
+
+            harness.world_mut(0).delete(entity);
+            harness.step();
+            assert!(harness.tree().get_node(node).is_none());
+            assert_eq!(harness.renderable_count(0), 0);
+        }
+    }
+
+    fn cube_harness() -> (TestHarness<i32>, Log) {
+        let mut models = Models::<i32>::default();
+        models.insert_headless(Some("Cube"), None, Template::None);
+        models.set_spatial(0, true);
+        (TestHarness::new(models), Log::default())
+    }
+
+    fn cube() -> Renderable {
+        Renderable::new_3d(Position3D::default(), 0, Template::None)
+    }
+
+    #[test]
+    fn spatial_renderables_sync_their_3d_transform() {
+        let (mut harness, log) = cube_harness();
+        harness.push(LogState::new("A", &log));
+        let entity = harness.world_mut(0).insert((), vec![(cube(), Position3D::new(1f32, 2f32, 3f32))])[0];
+        harness.step();
+
+        let (container_node, model_node) = {
+            let renderable = harness.world(0).get_component::<Renderable>(entity).unwrap();
+            (renderable.container_node.unwrap(), renderable.renderable_node.unwrap())
+        };
+        let container = harness.tree().get_node(container_node).unwrap();
+        assert_eq!(container.kind, NodeKind::Spatial);
+        assert_eq!(container.transform_3d, Some(Position3D::new(1f32, 2f32, 3f32)));
+        assert_eq!(harness.tree().get_node(model_node).unwrap().kind, NodeKind::Spatial);
+
+        harness.world_mut(0).get_component_mut::<Position3D>(entity).unwrap().translation.z = -10f32;
+        harness.step();
+        let container = harness.tree().get_node(container_node).unwrap();
+        assert_eq!(container.transform_3d, Some(Position3D::new(1f32, 2f32, -10f32)));
+    }
+
+    #[test]
+    fn spatial_renderables_are_hidden_with_their_covered_state() {
+        for policy in vec![CoverPolicy::Hide, CoverPolicy::HideAndFreeze] {
+            let (mut harness, log) = cube_harness();
+            harness.push(LogState::new("A", &log));
+            let entities = harness.world_mut(0).insert((), vec![(cube(), Position3D::default()), (cube(), Position3D::default())]).to_vec();
+            harness.step();
+            let containers: Vec<SceneNode> = entities.iter()
+                .map(|entity| harness.world(0).get_component::<Renderable>(*entity).unwrap().container_node.unwrap())
+                .collect();
+            // The second cube is hidden by the game and should stay hidden when its state is uncovered
+            harness.tree_mut().set_visible(containers[1], false);
+
+            harness.push(LogState::new("Menu", &log).covering(policy));
+            for container in containers.iter() {
+                assert!(!harness.tree().is_visible_in_tree(container.headless().unwrap()));
+            }
+
+            send(&mut harness, || Trans::Pop);
+            harness.step();
+            assert!(harness.tree().is_visible_in_tree(containers[0].headless().unwrap()));
+            assert!(!harness.tree().is_visible_in_tree(containers[1].headless().unwrap()));
+        }
+    }
+
+    #[test]
+    fn hiding_a_node_hides_the_nodes_of_other_kinds_below_it() {
+        let mut backend = HeadlessBackend::new();
+        let root = backend.root();
+        let canvas = backend.create_node(root, "Canvas", NodeKind::Node2D);
+        let plain = backend.create_node(canvas, "Plain", NodeKind::Node);
+        let spatial = backend.create_node(canvas, "Spatial", NodeKind::Spatial);
+        let sprite = backend.create_node(plain, "Sprite", NodeKind::Node2D);
+        let nodes: Vec<usize> = vec![plain, spatial, sprite].iter().map(|node| node.headless().unwrap()).collect();
+
+        backend.set_visible(canvas, false);
+        assert!(nodes.iter().all(|node| !backend.is_visible_in_tree(*node)));
+        backend.set_visible(canvas, true);
+        assert!(nodes.iter().all(|node| backend.is_visible_in_tree(*node)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,262 @@
+use crate::prelude::*;
+
+/// The TestHarness runs a VermarineEngine on the HeadlessBackend so that states and systems can be tested with cargo test
+///
+/// Models\<T> has to be built with Models::insert_headless() as there is no godot to load scenes from.
+///
+/// Example testing that an enemy gets spawned within the first second:
+/// ```
+/// let mut models = Models::<Renderables>::default();
+/// models.insert_headless(Some("Player"), None, Template::ASprite(AnimSprite::default()));
+/// models.insert_headless(Some("Enemy"), None, Template::ASprite(AnimSprite::default()));
+///
+/// let mut harness = TestHarness::new(models);
+/// harness.push(Box::new(MainState { }));
+///
+/// harness.press_action("move_left");
+/// harness.step_n(60);
+///
+/// let enemies = <Read<EnemyComp>>::query().iter(harness.world(0)).count();
+/// assert!(enemies >= 1);
+/// ```
+pub struct TestHarness<T>
+    where T: Eq + std::hash::Hash + 'static {
+    engine: VermarineEngine<T>,
+    /// The delta passed to the engine each step, defaults to 1/60th of a second
+    pub delta: f64,
+}
+
+impl<T> TestHarness<T>
+    where T: Eq + std::hash::Hash + 'static {
+    pub fn new(models: Models<T>) -> Self {
+        let mut engine = VermarineEngine::<T>::headless();
+        engine.resources.insert(models);
+
+        TestHarness {
+            engine,
+            delta: 1f64 / 60f64,
+        }
+    }
+
+    pub fn engine(&self) -> &VermarineEngine<T> {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut VermarineEngine<T> {
+        &mut self.engine
+    }
+
+    pub fn resources(&mut self) -> &mut Resources {
+        &mut self.engine.resources
+    }
+
+    /// Pushes a state onto the stack immediately, this does not step the engine
+    pub fn push(&mut self, state: Box<dyn State>) -> &mut Self {
+        self.engine.push(state);
+        self
+    }
+
+    /// Runs one frame of the engine with the harness' delta
+    pub fn step(&mut self) -> &mut Self {
+        self.engine.tick(self.delta);
+        self
+    }
+
+    pub fn step_n(&mut self, frames: usize) -> &mut Self {
+        for _ in 0..frames {
+            self.step();
+        }
+        self
+    }
+
+    /// Holds an action down until release_action() is called, it will be just pressed on the next step
+    pub fn press_action(&mut self, action: &str) -> &mut Self {
+        self.engine.resources.get_mut::<InputState>().unwrap().press(action);
+        self
+    }
+
+    pub fn release_action(&mut self, action: &str) -> &mut Self {
+        self.engine.resources.get_mut::<InputState>().unwrap().release(action);
+        self
+    }
+
+    /// Presses an action for a single step and then releases it
+    pub fn tap_action(&mut self, action: &str) -> &mut Self {
+        self.press_action(action);
+        self.step();
+        self.release_action(action)
+    }
+
+    /// Sends a window close request to the engine the same way godot's NOTIFICATION_WM_QUIT_REQUEST would
+    pub fn request_quit(&mut self) -> &mut Self {
+        self.engine.request_quit();
+        self
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.engine.is_finished()
+    }
+
+    /// Returns the StateData of the state at index in the stack, 0 is the bottom of the stack
+    ///
+    /// # Panics
+    ///
+    /// This will panic if there is no state at index
+    pub fn state_data(&self, index: usize) -> &StateData {
+        match self.engine.state_data(index) {
+            Some(data) => data,
+            None => panic!("Attempt to access StateData at stack index {} failed", index),
+        }
+    }
+
+    /// Returns the world of the state at index in the stack, 0 is the bottom of the stack
+    ///
+    /// # Panics
+    ///
+    /// This will panic if there is no state at index
+    pub fn world(&self, index: usize) -> &LWorld {
+        &self.state_data(index).world
+    }
+
+    pub fn world_mut(&mut self, index: usize) -> &mut LWorld {
+        match self.engine.state_data_mut(index) {
+            Some(data) => &mut data.world,
+            None => panic!("Attempt to access StateData at stack index {} failed", index),
+        }
+    }
+
+    /// The fake node tree that the engine has built
+    pub fn tree(&self) -> &HeadlessBackend {
+        self.engine.backend_as::<HeadlessBackend>().unwrap()
+    }
+
+    pub fn tree_mut(&mut self) -> &mut HeadlessBackend {
+        self.engine.backend_as_mut::<HeadlessBackend>().unwrap()
+    }
+
+    /// Returns the index in the tree of the RenderablesContainer node of the state at index in the stack
+    pub fn container(&self, index: usize) -> Option<usize> {
+        self.engine.state_data(index)?.containernode?.headless()
+    }
+
+    /// Returns the number of entity nodes that have been created for the state at index in the stack
+    pub fn renderable_count(&self, index: usize) -> usize {
+        match self.container(index) {
+            Some(container) => self.tree().get(container).map_or(0, |node| node.children.len()),
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::prelude::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    pub(crate) type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Records every state method that gets called on it, other modules use it to test the engine through the harness
+    pub(crate) struct LogState {
+        name: &'static str,
+        log: Log,
+        quit_request: fn() -> Trans,
+        cover_policy: CoverPolicy,
+    }
+
+    impl LogState {
+        pub(crate) fn new(name: &'static str, log: &Log) -> Box<LogState> {
+            Box::new(LogState { name, log: log.clone(), quit_request: || Trans::Quit, cover_policy: CoverPolicy::KeepRunning })
+        }
+
+        pub(crate) fn covering(mut self: Box<Self>, cover_policy: CoverPolicy) -> Box<Self> {
+            self.cover_policy = cover_policy;
+            self
+        }
+
+        /// Makes on_quit_request return the Trans instead of Trans::Quit
+        pub(crate) fn answering_quit_with(mut self: Box<Self>, quit_request: fn() -> Trans) -> Box<Self> {
+            self.quit_request = quit_request;
+            self
+        }
+
+        fn record(&self, event: &str) {
+            self.log.borrow_mut().push(format!("{} {}", self.name, event));
+        }
+    }
+
+    impl State for LogState {
+        fn on_push(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.record("push") }
+        fn on_pop(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.record("pop") }
+        fn on_cover(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.record("cover") }
+        fn on_uncover(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.record("uncover") }
+        fn on_uncover_with(&mut self, _data: &mut StateData, _resources: &mut Resources, result: Box<dyn std::any::Any>) {
+            match result.downcast::<i32>() {
+                Ok(result) => self.record(&format!("uncover with {}", result)),
+                Err(_) => self.record("uncover with something else"),
+            }
+        }
+        fn on_quit(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.record("quit") }
+        fn on_quit_request(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Trans {
+            self.record("quit request");
+            (self.quit_request)()
+        }
+        fn update(&mut self, _data: &mut StateData, resources: &mut Resources) {
+            if resources.get::<InputState>().unwrap().is_action_just_pressed("jump") {
+                self.record("jump");
+            }
+        }
+        fn cover_policy(&mut self, _data: &mut StateData, _resources: &mut Resources) -> CoverPolicy { self.cover_policy }
+        fn get_name(&mut self, _data: &mut StateData, _resources: &mut Resources) -> String { self.name.into() }
+    }
+
+    pub(crate) fn harness() -> (TestHarness<i32>, Log) {
+        (TestHarness::new(Models::default()), Rc::new(RefCell::new(vec![])))
+    }
+
+    pub(crate) fn send<F>(harness: &mut TestHarness<i32>, trans: F)
+        where F: FnOnce() -> Trans + 'static {
+        harness.resources().get::<TransResource>().unwrap().send(trans).unwrap();
+    }
+
+    pub(crate) fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn push_creates_the_state_nodes() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let container = harness.tree().find("Layer: game/State0: A/RenderablesContainer");
+        assert!(container.is_some());
+        assert_eq!(harness.container(0), container);
+        assert_eq!(harness.renderable_count(0), 0);
+        assert_eq!(take(&log), vec!["A push"]);
+    }
+
+    #[test]
+    fn tapped_actions_are_just_pressed_for_one_step() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        take(&log);
+
+        harness.tap_action("jump");
+        harness.step_n(2);
+        assert_eq!(take(&log), vec!["A jump"]);
+    }
+
+    #[test]
+    fn held_actions_are_only_just_pressed_once() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        take(&log);
+
+        harness.press_action("jump").step_n(3);
+        assert_eq!(take(&log), vec!["A jump"]);
+
+        harness.release_action("jump").step();
+        harness.press_action("jump").step();
+        assert_eq!(take(&log), vec!["A jump"]);
+    }
+}
@@ -0,0 +1,459 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// Makes an entity the child of another entity in the same world.
+///
+/// The Position of a child is relative to its parent, the engine nests the child's container node inside of the parent's
+/// and works out the GlobalPosition of every entity in the hierarchy at the start of every frame.
+/// If the parent has no Renderable the child's container is placed under the state's container at its GlobalPosition instead.
+/// Deleting the parent deletes all of its children along with it.
+///
+/// Example of putting a turret on a tank:
+/// ```
+/// let tank = commands.insert((), vec![(
+///     Renderable::new(Position::default(), tank_model.1, tank_model.0),
+///     Position::new(200f32, 200f32),
+///     Tank { speed: 100f32 },
+/// )])[0];
+///
+/// commands.insert((), vec![(
+///     Renderable::new(Position::default(), turret_model.1, turret_model.0),
+///     Position::new(0f32, -12f32),
+///     Parent { entity: tank },
+///     Turret { fire_rate: 2f32 },
+/// )]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parent {
+    pub entity: Entity,
+}
+
+/// Where an entity with a Position ends up after the Positions of all of its parents are applied.
+///
+/// This is written by the engine at the start of every frame and again before the world is synced to godot,
+/// it is only overwritten if it changed. Call propagate_transforms() to update it in the middle of a frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalPosition {
+    pub transform: Transform2D,
+}
+
+impl GlobalPosition {
+    pub fn x(&self) -> f32 {
+        self.transform.m31
+    }
+
+    pub fn y(&self) -> f32 {
+        self.transform.m32
+    }
+}
+
+/// Where an entity with a Position3D ends up after the Position3Ds of all of its parents are applied, see GlobalPosition
+#[derive(Clone, Copy)]
+pub struct GlobalPosition3D {
+    pub transform: Transform,
+}
+
+impl GlobalPosition3D {
+    pub fn translation(&self) -> Vector3 {
+        self.transform.origin
+    }
+}
+
+/// Hierarchies deeper than this are assumed to be a cycle of parents and are cut off
+const MAX_DEPTH: usize = 256;
+
+/// Returns every entity whose Parent is the entity
+pub fn children_of(world: &mut LWorld, entity: Entity) -> Vec<Entity> {
+    <Read<Parent>>::query()
+        .iter_entities(&mut *world)
+        .filter(|(_, parent)| parent.entity == entity)
+        .map(|(child, _)| child)
+        .collect()
+}
+
+/// Deletes the entity along with its children, their children and so on
+pub fn despawn_recursive(world: &mut LWorld, entity: Entity) {
+    for child in children_of(world, entity) {
+        despawn_recursive(world, child);
+    }
+    world.delete(entity);
+}
+
+/// Works out the GlobalPosition and GlobalPosition3D of every entity with a Position or Position3D,
+/// the engine calls this for every state's world and the global world at the start of every frame before any schedule is run
+pub fn propagate_transforms(world: &mut LWorld) {
+    let parents: HashMap<Entity, Entity> = <Read<Parent>>::query()
+        .iter_entities(&mut *world)
+        .map(|(entity, parent)| (entity, parent.entity))
+        .collect();
+
+    let locals: HashMap<Entity, Transform2D> = <Read<Position>>::query()
+        .iter_entities(&mut *world)
+        .map(|(entity, pos)| (entity, pos.to_transform2d()))
+        .collect();
+    let globals = resolve(&locals, &parents, |parent, local| local.post_transform(parent));
+    for (entity, transform) in globals {
+        let global = GlobalPosition { transform };
+        let existing = world.get_component::<GlobalPosition>(entity).map(|existing| *existing);
+        match existing {
+            Some(existing) if existing == global => {},
+            Some(_) => *world.get_component_mut::<GlobalPosition>(entity).unwrap() = global,
+            None => { let _ = world.add_component(entity, global); },
+        }
+    }
+
+    let locals: HashMap<Entity, Transform> = <Read<Position3D>>::query()
+        .iter_entities(&mut *world)
+        .map(|(entity, pos)| (entity, pos.to_transform()))
+        .collect();
+    let globals = resolve(&locals, &parents, |parent, local| mul_transform(parent, local));
+    for (entity, transform) in globals {
+        let existing = world.get_component::<GlobalPosition3D>(entity).map(|existing| existing.transform);
+        match existing {
+            Some(existing) if same_transform(&existing, &transform) => {},
+            Some(_) => world.get_component_mut::<GlobalPosition3D>(entity).unwrap().transform = transform,
+            None => { let _ = world.add_component(entity, GlobalPosition3D { transform }); },
+        }
+    }
+}
+
+/// Godot's Transform doesn't implement PartialEq
+fn same_transform(a: &Transform, b: &Transform) -> bool {
+    a.origin == b.origin && a.basis.elements == b.basis.elements
+}
+
+/// Walks up the parents of each entity applying their local transforms, parents without a transform are skipped over
+fn resolve<T, F>(locals: &HashMap<Entity, T>, parents: &HashMap<Entity, Entity>, combine: F) -> HashMap<Entity, T>
+    where T: Copy, F: Fn(&T, &T) -> T {
+    let mut globals = HashMap::new();
+    for (entity, local) in locals.iter() {
+        let mut global = *local;
+        let mut current = *entity;
+        for _ in 0..MAX_DEPTH {
+            current = match parents.get(&current) {
+                Some(parent) => *parent,
+                None => break,
+            };
+            if let Some(parent) = locals.get(&current) {
+                global = combine(parent, &global);
+            }
+        }
+        globals.insert(*entity, global);
+    }
+    globals
+}
+
+fn mul_transform(parent: &Transform, local: &Transform) -> Transform {
+    let rows = |t: &Transform| [
+        [t.basis.elements[0].x, t.basis.elements[0].y, t.basis.elements[0].z],
+        [t.basis.elements[1].x, t.basis.elements[1].y, t.basis.elements[1].z],
+        [t.basis.elements[2].x, t.basis.elements[2].y, t.basis.elements[2].z],
+    ];
+    let (a, b) = (rows(parent), rows(local));
+    let row = |i: usize| Vector3::new(
+        a[i][0] * b[0][0] + a[i][1] * b[1][0] + a[i][2] * b[2][0],
+        a[i][0] * b[0][1] + a[i][1] * b[1][1] + a[i][2] * b[2][1],
+        a[i][0] * b[0][2] + a[i][1] * b[1][2] + a[i][2] * b[2][2],
+    );
+    let origin = local.origin;
+    let moved = |i: usize| a[i][0] * origin.x + a[i][1] * origin.y + a[i][2] * origin.z;
+    Transform {
+        basis: Basis { elements: [row(0), row(1), row(2)] },
+        origin: Vector3::new(moved(0), moved(1), moved(2)) + parent.origin,
+    }
+}
+
+/// Deletes every entity whose parent no longer exists, this repeats until grandchildren and so on are deleted too
+pub(crate) fn despawn_orphans(world: &mut LWorld) {
+    loop {
+        let parents: Vec<(Entity, Entity)> = <Read<Parent>>::query()
+            .iter_entities(&mut *world)
+            .map(|(entity, parent)| (entity, parent.entity))
+            .collect();
+        let orphans: Vec<Entity> = parents.into_iter()
+            .filter(|(_, parent)| !world.is_alive(*parent))
+            .map(|(entity, _)| entity)
+            .collect();
+
+        if orphans.is_empty() {
+            break;
+        }
+        for orphan in orphans {
+            world.delete(orphan);
+        }
+    }
+}
+
+/// Nests the container of every child entity inside of its parent's container,
+/// entities whose parent has no container and entities that lost their Parent are put back under the state's container.
+/// Containers that are nested are given their local Position again as it was only placed in the state's container before
+pub(crate) fn sync_hierarchy(
+    backend: &mut dyn SceneBackend,
+    world: &mut LWorld,
+    containernode: SceneNode,
+    node_lookup: &HashMap<Entity, SceneNode>) {
+
+    let parents: HashMap<Entity, Entity> = <Read<Parent>>::query()
+        .iter_entities(&mut *world)
+        .map(|(entity, parent)| (entity, parent.entity))
+        .collect();
+
+    let moves: Vec<(Entity, Option<Entity>)> = <Read<Renderable>>::query()
+        .iter_entities(&mut *world)
+        .filter_map(|(entity, renderable)| {
+            let nest_in = parents.get(&entity).cloned().filter(|parent| node_lookup.contains_key(parent));
+            if renderable.nested_in != nest_in && renderable.container_node.is_some() {
+                Some((entity, nest_in))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (entity, nest_in) in moves {
+        let target = nest_in.and_then(|parent| node_lookup.get(&parent)).cloned().unwrap_or(containernode);
+        let node = match world.get_component_mut::<Renderable>(entity) {
+            Some(mut renderable) => {
+                backend.reparent(renderable.container_node.unwrap(), target);
+                renderable.nested_in = nest_in;
+                renderable.container_node.unwrap()
+            },
+            None => continue,
+        };
+
+        // Nested containers are placed relative to their parent's container again,
+        // containers moved out to the state's container are placed by sync_unnested()
+        if nest_in.is_some() {
+            if let Some(pos) = world.get_component::<Position>(entity) {
+                backend.set_transform(node, &pos);
+            }
+            if let Some(pos) = world.get_component::<Position3D>(entity) {
+                backend.set_transform_3d(node, &pos);
+            }
+        }
+    }
+}
+
+/// Returns the container of every child entity that isn't nested inside of its parent's container,
+/// this happens when the parent has no Renderable and the child's container is placed under the state's container instead
+pub(crate) fn unnested_children(world: &mut LWorld) -> HashMap<Entity, SceneNode> {
+    <(Read<Parent>, Read<Renderable>)>::query()
+        .iter_entities(&mut *world)
+        .filter(|(_, (_, renderable))| renderable.nested_in.is_none())
+        .filter_map(|(entity, (_, renderable))| renderable.container_node.map(|node| (entity, node)))
+        .collect()
+}
+
+/// Places the containers of children that aren't nested inside of their parent's container using their GlobalPosition and GlobalPosition3D,
+/// so that they still follow their parents. The z_index isn't part of the GlobalPosition so it is taken from the child's Position
+pub(crate) fn sync_unnested(backend: &mut dyn SceneBackend, world: &LWorld, unnested: &HashMap<Entity, SceneNode>) {
+    for (entity, node) in unnested.iter() {
+        if let Some(global) = world.get_component::<GlobalPosition>(*entity) {
+            let mut pos = Position::from_transform2d(&global.transform);
+            if let Some(local) = world.get_component::<Position>(*entity) {
+                pos.z_index = local.z_index;
+                pos.z_as_relative = local.z_as_relative;
+            }
+            backend.set_transform(*node, &pos);
+        }
+        if let Some(global) = world.get_component::<GlobalPosition3D>(*entity) {
+            backend.set_transform_3d(*node, &Position3D::from_transform(&global.transform));
+        }
+    }
+}
+
+/// Moves nodes out of the containers of entities that are about to be freed so that no node is freed twice
+/// and the containers of children that are still alive aren't freed along with their parent's
+pub(crate) fn unnest_removed(
+    backend: &mut dyn SceneBackend,
+    world: &mut LWorld,
+    containernode: SceneNode,
+    removed: &[(Entity, SceneNode)]) {
+
+    for (_, node) in removed.iter() {
+        if backend.get_parent(*node) != Some(containernode) {
+            backend.reparent(*node, containernode);
+        }
+    }
+
+    let nested: Vec<Entity> = <Read<Renderable>>::query()
+        .iter_entities(&mut *world)
+        .filter(|(_, renderable)| renderable.nested_in.map_or(false, |parent| removed.iter().any(|(entity, _)| *entity == parent)))
+        .map(|(entity, _)| entity)
+        .collect();
+
+    for entity in nested {
+        if let Some(mut renderable) = world.get_component_mut::<Renderable>(entity) {
+            if let Some(node) = renderable.container_node {
+                backend.reparent(node, containernode);
+            }
+            renderable.nested_in = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    struct EmptyState;
+
+    impl State for EmptyState {}
+
+    fn square_harness() -> TestHarness<i32> {
+        let mut models = Models::<i32>::default();
+        models.insert_headless(Some("Square"), None, Template::None);
+        let mut harness = TestHarness::new(models);
+        harness.push(Box::new(EmptyState));
+        harness
+    }
+
+    fn square() -> Renderable {
+        Renderable::new(Position::default(), 0, Template::None)
+    }
+
+    #[test]
+    fn global_positions_apply_every_parent() {
+        let universe = Universe::new();
+        let mut world = universe.create_world();
+        let parent = world.insert((), vec![(Position::new(10f32, 0f32).with_rotation(euclid::Angle::degrees(90f32)),)])[0];
+        let child = world.insert((), vec![(Position::new(5f32, 0f32), Parent { entity: parent })])[0];
+        let grandchild = world.insert((), vec![(Position::new(0f32, 1f32), Parent { entity: child })])[0];
+
+        propagate_transforms(&mut world);
+        let global = *world.get_component::<GlobalPosition>(child).unwrap();
+        assert!((global.x() - 10f32).abs() < 1e-4 && (global.y() - 5f32).abs() < 1e-4);
+        let global = *world.get_component::<GlobalPosition>(grandchild).unwrap();
+        assert!((global.x() - 9f32).abs() < 1e-4 && (global.y() - 5f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn global_positions_3d_apply_every_parent() {
+        let universe = Universe::new();
+        let mut world = universe.create_world();
+        let parent = world.insert((), vec![(Position3D::new(1f32, 2f32, 3f32),)])[0];
+        let child = world.insert((), vec![(Position3D::new(1f32, 0f32, 0f32), Parent { entity: parent })])[0];
+
+        propagate_transforms(&mut world);
+        let translation = world.get_component::<GlobalPosition3D>(child).unwrap().translation();
+        assert_eq!(translation, Vector3::new(2f32, 2f32, 3f32));
+    }
+
+    #[test]
+    fn despawn_recursive_deletes_every_descendant() {
+        let universe = Universe::new();
+        let mut world = universe.create_world();
+        let parent = world.insert((), vec![(Position::default(),)])[0];
+        let child = world.insert((), vec![(Parent { entity: parent },)])[0];
+        let grandchild = world.insert((), vec![(Parent { entity: child },)])[0];
+        let unrelated = world.insert((), vec![(Position::default(),)])[0];
+
+        despawn_recursive(&mut world, parent);
+        assert!(!world.is_alive(child) && !world.is_alive(grandchild));
+        assert!(world.is_alive(unrelated));
+    }
+
+    #[test]
+    fn children_are_nested_in_their_parents_container() {
+        let mut harness = square_harness();
+        let parent = harness.world_mut(0).insert((), vec![(square(), Position::new(10f32, 10f32))])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::new(0f32, 5f32), Parent { entity: parent })])[0];
+        harness.step();
+
+        let container = |entity: Entity| harness.world(0).get_component::<Renderable>(entity).unwrap().container_node.unwrap();
+        let child_parent = harness.tree().get_node(container(child)).unwrap().parent;
+        assert_eq!(child_parent, container(parent).headless());
+        assert_eq!(harness.renderable_count(0), 1);
+    }
+
+    #[test]
+    fn deleting_a_parent_deletes_its_children() {
+        let mut harness = square_harness();
+        let parent = harness.world_mut(0).insert((), vec![(square(), Position::default())])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::default(), Parent { entity: parent })])[0];
+        harness.step();
+
+        harness.world_mut(0).delete(parent);
+        harness.step();
+        assert!(!harness.world(0).is_alive(child));
+        assert_eq!(harness.renderable_count(0), 0);
+    }
+
+    fn transform(harness: &TestHarness<i32>, entity: Entity) -> Position {
+        let node = harness.world(0).get_component::<Renderable>(entity).unwrap().container_node.unwrap();
+        harness.tree().get_node(node).unwrap().transform.unwrap()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn nested_children_are_placed_with_their_local_position() {
+        let mut harness = square_harness();
+        let parent = harness.world_mut(0).insert((), vec![(square(), Position::new(10f32, 10f32))])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::new(0f32, 5f32), Parent { entity: parent })])[0];
+        harness.step();
+
+        let pos = transform(&harness, child);
+        assert!(close(pos.x, 0f32) && close(pos.y, 5f32));
+    }
+
+    #[test]
+    fn children_of_parents_without_a_renderable_follow_their_global_position() {
+        let mut harness = square_harness();
+        let parent = harness.world_mut(0).insert((), vec![(Position::new(10f32, 10f32).with_rotation(euclid::Angle::degrees(90f32)),)])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::new(5f32, 0f32).with_z_index(2), Parent { entity: parent })])[0];
+        harness.step();
+
+        assert_eq!(harness.renderable_count(0), 1);
+        let pos = transform(&harness, child);
+        assert!(close(pos.x, 10f32) && close(pos.y, 15f32));
+        assert!(close(pos.rotation.get(), std::f32::consts::FRAC_PI_2));
+        assert_eq!(pos.z_index, 2);
+
+        harness.world_mut(0).get_component_mut::<Position>(parent).unwrap().x = 20f32;
+        harness.step();
+        let pos = transform(&harness, child);
+        assert!(close(pos.x, 20f32) && close(pos.y, 15f32));
+    }
+
+    #[test]
+    fn children_are_placed_globally_when_their_parent_loses_its_renderable() {
+        let mut harness = square_harness();
+        let parent = harness.world_mut(0).insert((), vec![(square(), Position::new(10f32, 10f32))])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::new(0f32, 5f32), Parent { entity: parent })])[0];
+        harness.step();
+
+        let _ = harness.world_mut(0).remove_component::<Renderable>(parent);
+        harness.step();
+        assert_eq!(harness.renderable_count(0), 1);
+        let pos = transform(&harness, child);
+        assert!(close(pos.x, 10f32) && close(pos.y, 15f32));
+
+        harness.world_mut(0).add_component(parent, square()).unwrap();
+        harness.step();
+        let pos = transform(&harness, child);
+        assert!(close(pos.x, 0f32) && close(pos.y, 5f32));
+    }
+
+    #[test]
+    fn children_3d_of_parents_without_a_renderable_follow_their_global_position() {
+        let mut models = Models::<i32>::default();
+        models.insert_headless(Some("Cube"), None, Template::None);
+        models.set_spatial(0, true);
+        let mut harness = TestHarness::new(models);
+        harness.push(Box::new(EmptyState));
+        let parent = harness.world_mut(0).insert((), vec![(Position3D::new(1f32, 2f32, 3f32),)])[0];
+        let child = harness.world_mut(0).insert((), vec![(
+            Renderable::new_3d(Position3D::default(), 0, Template::None),
+            Position3D::new(1f32, 0f32, 0f32),
+            Parent { entity: parent },
+        )])[0];
+        harness.step();
+
+        let node = harness.world(0).get_component::<Renderable>(child).unwrap().container_node.unwrap();
+        let pos = harness.tree().get_node(node).unwrap().transform_3d.unwrap();
+        assert_eq!(pos.translation, Vector3::new(2f32, 2f32, 3f32));
+    }
+}
@@ -0,0 +1,551 @@
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// The InputState resource holds the input for the current frame.
+///
+/// The engine fills it in from the backend at the start of every frame, with the GodotBackend every action in godot's InputMap is polled
+/// and the mouse and text input come from the events passed to the engine's _input.
+/// States and systems should read input from here rather than from Input::godot_singleton() so that they can be run on the HeadlessBackend.
+///
+/// The states in the stack are updated from the top down and a state can consume input so that the states below it and their systems don't see it,
+/// consumed input is hidden from the next state onwards so the state that consumed it and its own systems still see it.
+/// Consumed input is reset at the end of every frame.
+/// ```
+/// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     let mut input = resources.get_mut::<InputState>().unwrap();
+///     if input.is_action_just_pressed("pause") {
+///         input.consume_action("pause");
+///         // --snip
+///     }
+/// }
+/// ```
+///
+/// Example of reading input inside of a system:
+/// ```
+/// SystemBuilder::<()>::new("ExampleSystem")
+///     .read_resource::<InputState>()
+///     .build(move |commands, world, input, queries| {
+///         if input.is_action_just_pressed("pause") {
+///             // --snip
+///         }
+///
+///         let horizontal = input.get_axis("move_left", "move_right");
+///         if input.is_mouse_button_just_pressed(MOUSE_BUTTON_LEFT) {
+///             let target = input.mouse_position();
+///             // --snip
+///         }
+///     })
+/// ```
+#[derive(Clone, Debug)]
+pub struct InputState {
+    pressed: HashSet<String>,
+    previous: HashSet<String>,
+    strengths: HashMap<String, f32>,
+    mouse_position: Vector2,
+    mouse_buttons: HashSet<i64>,
+    previous_mouse_buttons: HashSet<i64>,
+    text: String,
+    joy_axes: HashMap<(i64, i64), f32>,
+    joypads: Vec<i64>,
+    joypad_events: Vec<JoypadEvent>,
+    touch: Option<(Vector2, Vector2)>,
+    axes: HashMap<String, VirtualAxis>,
+    sticks: HashMap<String, VirtualStick>,
+    last_pressed: Option<Binding>,
+    consumed: ConsumedInput,
+    pending: ConsumedInput,
+}
+
+/// Input that has been consumed by a state
+#[derive(Clone, Debug, Default)]
+struct ConsumedInput {
+    all: bool,
+    actions: HashSet<String>,
+    mouse_buttons: HashSet<i64>,
+    text: bool,
+}
+
+impl ConsumedInput {
+    fn merge(&mut self, other: ConsumedInput) {
+        self.all = self.all || other.all;
+        self.actions.extend(other.actions);
+        self.mouse_buttons.extend(other.mouse_buttons);
+        self.text = self.text || other.text;
+    }
+}
+
+/// The godot button index of the left mouse button
+pub const MOUSE_BUTTON_LEFT: i64 = 1;
+/// The godot button index of the right mouse button
+pub const MOUSE_BUTTON_RIGHT: i64 = 2;
+/// The godot button index of the middle mouse button
+pub const MOUSE_BUTTON_MIDDLE: i64 = 3;
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState {
+            pressed: HashSet::new(),
+            previous: HashSet::new(),
+            strengths: HashMap::new(),
+            mouse_position: Vector2::new(0f32, 0f32),
+            mouse_buttons: HashSet::new(),
+            previous_mouse_buttons: HashSet::new(),
+            text: String::new(),
+            joy_axes: HashMap::new(),
+            joypads: vec![],
+            joypad_events: vec![],
+            touch: None,
+            axes: HashMap::new(),
+            sticks: HashMap::new(),
+            last_pressed: None,
+            consumed: ConsumedInput::default(),
+            pending: ConsumedInput::default(),
+        }
+    }
+}
+
+impl InputState {
+    pub fn is_action_pressed(&self, action: &str) -> bool {
+        !self.is_action_consumed(action) && self.pressed.contains(action)
+    }
+
+    /// Returns true if the action is pressed this frame and was not pressed last frame
+    pub fn is_action_just_pressed(&self, action: &str) -> bool {
+        !self.is_action_consumed(action) && self.pressed.contains(action) && !self.previous.contains(action)
+    }
+
+    /// Returns true if the action was pressed last frame and is not pressed this frame
+    pub fn is_action_just_released(&self, action: &str) -> bool {
+        !self.is_action_consumed(action) && !self.pressed.contains(action) && self.previous.contains(action)
+    }
+
+    /// How far the action is pressed from 0 to 1, this is only ever between 0 and 1 for analog inputs such as triggers and sticks
+    pub fn get_action_strength(&self, action: &str) -> f32 {
+        if self.is_action_consumed(action) {
+            return 0f32;
+        }
+
+        match self.strengths.get(action) {
+            Some(strength) => *strength,
+            None if self.pressed.contains(action) => 1f32,
+            None => 0f32,
+        }
+    }
+
+    /// Returns the strength of the positive action minus the strength of the negative action, from -1 to 1
+    pub fn get_axis(&self, negative: &str, positive: &str) -> f32 {
+        self.get_action_strength(positive) - self.get_action_strength(negative)
+    }
+
+    /// The position of the mouse relative to the viewport
+    pub fn mouse_position(&self) -> Vector2 {
+        self.mouse_position
+    }
+
+    /// Takes godot's button index, see MOUSE_BUTTON_LEFT, MOUSE_BUTTON_RIGHT and MOUSE_BUTTON_MIDDLE
+    pub fn is_mouse_button_pressed(&self, button: i64) -> bool {
+        !self.is_mouse_button_consumed(button) && self.mouse_buttons.contains(&button)
+    }
+
+    pub fn is_mouse_button_just_pressed(&self, button: i64) -> bool {
+        !self.is_mouse_button_consumed(button) && self.mouse_buttons.contains(&button) && !self.previous_mouse_buttons.contains(&button)
+    }
+
+    pub fn is_mouse_button_just_released(&self, button: i64) -> bool {
+        !self.is_mouse_button_consumed(button) && !self.mouse_buttons.contains(&button) && self.previous_mouse_buttons.contains(&button)
+    }
+
+    /// The text typed since the last frame
+    pub fn text(&self) -> &str {
+        if self.consumed.all || self.consumed.text {
+            return "";
+        }
+        &self.text
+    }
+
+    /// Adds a named axis that combines several sources, see VirtualAxis
+    pub fn define_axis(&mut self, name: &str, axis: VirtualAxis) {
+        self.axes.insert(name.into(), axis);
+    }
+
+    /// Adds a named 2D stick that combines several sources, see VirtualStick
+    pub fn define_stick(&mut self, name: &str, stick: VirtualStick) {
+        self.sticks.insert(name.into(), stick);
+    }
+
+    /// The value of an axis added with define_axis() from -1 to 1, this is 0 if there is no axis with that name
+    pub fn axis(&self, name: &str) -> f32 {
+        self.axes.get(name).map_or(0f32, |axis| axis.value(self))
+    }
+
+    /// The value of a stick added with define_stick(), the vector is never longer than 1 and is zero if there is no stick with that name
+    ///
+    /// Example of moving the player with the same code for keyboard, gamepad and touch:
+    /// ```
+    /// for (takes_input, mut vel) in query.iter_mut(&mut *world) {
+    ///     let movement = input.stick("move") * takes_input.speed;
+    ///     vel.x = movement.x;
+    ///     vel.y = movement.y;
+    /// }
+    /// ```
+    pub fn stick(&self, name: &str) -> Vector2 {
+        self.sticks.get(name).map_or(Vector2::new(0f32, 0f32), |stick| stick.value(self))
+    }
+
+    /// The value of a gamepad's analog axis from -1 to 1 without any deadzone
+    pub fn get_joy_axis(&self, device: i64, axis: i64) -> f32 {
+        if self.consumed.all {
+            return 0f32;
+        }
+        self.joy_axes.get(&(device, axis)).cloned().unwrap_or(0f32)
+    }
+
+    /// The value of the axis of whichever connected gamepad is pushing it the furthest
+    pub fn get_joy_axis_any(&self, axis: i64) -> f32 {
+        if self.consumed.all {
+            return 0f32;
+        }
+        self.joy_axes.iter()
+            .filter(|((_, other), _)| *other == axis)
+            .map(|(_, value)| *value)
+            .fold(0f32, |strongest, value| if value.abs() > strongest.abs() { value } else { strongest })
+    }
+
+    pub fn set_joy_axis(&mut self, device: i64, axis: i64, value: f32) {
+        self.joy_axes.insert((device, axis), value.max(-1f32).min(1f32));
+    }
+
+    /// The device ids of every connected gamepad
+    pub fn connected_joypads(&self) -> &[i64] {
+        &self.joypads
+    }
+
+    /// Gamepads that were plugged in or unplugged this frame
+    pub fn joypad_events(&self) -> &[JoypadEvent] {
+        &self.joypad_events
+    }
+
+    /// Sets which gamepads are connected, gamepads that weren't connected before get a JoypadEvent::Connected
+    /// and gamepads that are missing get a JoypadEvent::Disconnected and have their axes reset
+    pub fn set_connected_joypads(&mut self, joypads: Vec<i64>) {
+        for device in joypads.iter() {
+            if !self.joypads.contains(device) {
+                self.joypad_events.push(JoypadEvent::Connected(*device));
+            }
+        }
+        for device in self.joypads.iter() {
+            if !joypads.contains(device) {
+                self.joypad_events.push(JoypadEvent::Disconnected(*device));
+                self.joy_axes.retain(|(axis_device, _), _| axis_device != device);
+            }
+        }
+        self.joypads = joypads;
+    }
+
+    /// Where the first touch currently is, this is None while the screen isn't being touched
+    pub fn touch_position(&self) -> Option<Vector2> {
+        if self.consumed.all {
+            return None;
+        }
+        self.touch.map(|(_, current)| current)
+    }
+
+    /// How far the first touch has moved since it started
+    pub fn touch_drag(&self) -> Option<Vector2> {
+        if self.consumed.all {
+            return None;
+        }
+        self.touch.map(|(start, current)| current - start)
+    }
+
+    pub fn begin_touch(&mut self, position: Vector2) {
+        self.touch = Some((position, position));
+    }
+
+    pub fn drag_touch(&mut self, position: Vector2) {
+        if let Some((_, current)) = self.touch.as_mut() {
+            *current = position;
+        }
+    }
+
+    pub fn end_touch(&mut self) {
+        self.touch = None;
+    }
+
+    /// The key, mouse button or joypad button pressed most recently this frame, a controls menu can bind an action to this with InputBindings
+    pub fn last_pressed(&self) -> Option<Binding> {
+        if self.consumed.all {
+            return None;
+        }
+        self.last_pressed
+    }
+
+    pub fn set_last_pressed(&mut self, binding: Binding) {
+        self.last_pressed = Some(binding);
+    }
+
+    /// Hides the action from the states below this one
+    pub fn consume_action(&mut self, action: &str) {
+        self.pending.actions.insert(action.into());
+    }
+
+    pub fn consume_mouse_button(&mut self, button: i64) {
+        self.pending.mouse_buttons.insert(button);
+    }
+
+    pub fn consume_text(&mut self) {
+        self.pending.text = true;
+    }
+
+    /// Hides every action, mouse button and the text from the states below this one, the mouse position is still visible
+    pub fn consume_all(&mut self) {
+        self.pending.all = true;
+    }
+
+    /// Returns true if a state above the one being updated consumed the action
+    pub fn is_action_consumed(&self, action: &str) -> bool {
+        self.consumed.all || self.consumed.actions.contains(action)
+    }
+
+    pub fn is_mouse_button_consumed(&self, button: i64) -> bool {
+        self.consumed.all || self.consumed.mouse_buttons.contains(&button)
+    }
+
+    /// Marks an action as held down until release() is called
+    pub fn press(&mut self, action: &str) {
+        self.pressed.insert(action.into());
+    }
+
+    pub fn release(&mut self, action: &str) {
+        self.pressed.remove(action);
+        self.strengths.remove(action);
+    }
+
+    /// Sets how far an action is pressed, a strength above 0 presses the action and a strength of 0 releases it
+    pub fn set_action_strength(&mut self, action: &str, strength: f32) {
+        let strength = strength.max(0f32).min(1f32);
+        if strength > 0f32 {
+            self.press(action);
+            self.strengths.insert(action.into(), strength);
+        } else {
+            self.release(action);
+        }
+    }
+
+    pub fn set_mouse_position(&mut self, position: Vector2) {
+        self.mouse_position = position;
+    }
+
+    pub fn press_mouse_button(&mut self, button: i64) {
+        self.mouse_buttons.insert(button);
+    }
+
+    pub fn release_mouse_button(&mut self, button: i64) {
+        self.mouse_buttons.remove(&button);
+    }
+
+    /// Adds typed text to this frame's text()
+    pub fn push_text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// Captures the raw input of this frame, this ignores any input consumed by states
+    pub fn snapshot(&self) -> InputSnapshot {
+        let mut actions: Vec<(String, f32)> = self.pressed.iter()
+            .map(|action| (action.clone(), self.strengths.get(action).cloned().unwrap_or(1f32)))
+            .collect();
+        actions.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut mouse_buttons: Vec<i64> = self.mouse_buttons.iter().cloned().collect();
+        mouse_buttons.sort();
+
+        let mut joy_axes: Vec<(i64, i64, f32)> = self.joy_axes.iter()
+            .map(|((device, axis), value)| (*device, *axis, *value))
+            .collect();
+        joy_axes.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        InputSnapshot {
+            actions,
+            mouse_position: self.mouse_position,
+            mouse_buttons,
+            text: self.text.clone(),
+            joy_axes,
+            touch: self.touch,
+            joypads: self.joypads.clone(),
+            joypad_events: self.joypad_events.clone(),
+            last_pressed: self.last_pressed,
+        }
+    }
+
+    /// Replaces this frame's input with the snapshot, last frame's input is kept so just pressed and just released still work
+    pub fn apply_snapshot(&mut self, snapshot: &InputSnapshot) {
+        self.pressed.clear();
+        self.strengths.clear();
+        for (action, strength) in snapshot.actions.iter() {
+            self.press(action);
+            self.strengths.insert(action.clone(), *strength);
+        }
+        self.mouse_position = snapshot.mouse_position;
+        self.mouse_buttons = snapshot.mouse_buttons.iter().cloned().collect();
+        self.text = snapshot.text.clone();
+        self.joy_axes = snapshot.joy_axes.iter().map(|(device, axis, value)| ((*device, *axis), *value)).collect();
+        self.touch = snapshot.touch;
+        self.joypads = snapshot.joypads.clone();
+        self.joypad_events = snapshot.joypad_events.clone();
+        self.last_pressed = snapshot.last_pressed;
+    }
+
+    /// Called by the engine before each state is updated so that input consumed by the states above it is hidden
+    pub(crate) fn begin_state(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.consumed.merge(pending);
+    }
+
+    /// Called by the engine after all states have been updated so that just pressed/released are relative to the last frame
+    pub(crate) fn end_frame(&mut self) {
+        self.previous = self.pressed.clone();
+        self.previous_mouse_buttons = self.mouse_buttons.clone();
+        self.text.clear();
+        self.last_pressed = None;
+        self.joypad_events.clear();
+        self.consumed = ConsumedInput::default();
+        self.pending = ConsumedInput::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::harness::tests::*;
+
+    /// Records the clicks and text it sees, it can consume the clicks or block every input from the states below it
+    struct InputLogState {
+        name: &'static str,
+        log: Log,
+        consume: bool,
+        blocks: bool,
+    }
+
+    impl InputLogState {
+        fn new(name: &'static str, log: &Log) -> Box<InputLogState> {
+            Box::new(InputLogState { name, log: log.clone(), consume: false, blocks: false })
+        }
+
+        fn consuming(mut self: Box<Self>) -> Box<Self> {
+            self.consume = true;
+            self
+        }
+
+        fn blocking(mut self: Box<Self>) -> Box<Self> {
+            self.blocks = true;
+            self
+        }
+    }
+
+    impl State for InputLogState {
+        fn update(&mut self, _data: &mut StateData, resources: &mut Resources) {
+            let input = resources.get::<InputState>().unwrap();
+            if input.is_mouse_button_just_pressed(MOUSE_BUTTON_LEFT) {
+                self.log.borrow_mut().push(format!("{} click", self.name));
+            }
+            if !input.text().is_empty() {
+                self.log.borrow_mut().push(format!("{} typed {}", self.name, input.text()));
+            }
+            drop(input);
+
+            if self.consume {
+                resources.get_mut::<InputState>().unwrap().consume_mouse_button(MOUSE_BUTTON_LEFT);
+            }
+        }
+        fn blocks_input(&mut self, _data: &mut StateData, _resources: &mut Resources) -> bool { self.blocks }
+    }
+
+    fn input(harness: &mut TestHarness<i32>) -> legion::systems::resource::FetchMut<'_, InputState> {
+        harness.resources().get_mut::<InputState>().unwrap()
+    }
+
+    #[test]
+    fn mouse_buttons_are_just_pressed_and_released_once() {
+        let mut input = InputState::default();
+        input.press_mouse_button(MOUSE_BUTTON_LEFT);
+        assert!(input.is_mouse_button_just_pressed(MOUSE_BUTTON_LEFT));
+
+        input.end_frame();
+        assert!(input.is_mouse_button_pressed(MOUSE_BUTTON_LEFT));
+        assert!(!input.is_mouse_button_just_pressed(MOUSE_BUTTON_LEFT));
+
+        input.release_mouse_button(MOUSE_BUTTON_LEFT);
+        assert!(input.is_mouse_button_just_released(MOUSE_BUTTON_LEFT));
+        input.end_frame();
+        assert!(!input.is_mouse_button_just_released(MOUSE_BUTTON_LEFT));
+    }
+
+    #[test]
+    fn action_strengths_are_clamped_and_make_axes() {
+        let mut input = InputState::default();
+        input.set_action_strength("move_right", 0.5f32);
+        input.set_action_strength("move_left", 2f32);
+        assert!(input.is_action_pressed("move_right"));
+        assert_eq!(input.get_action_strength("move_left"), 1f32);
+        assert_eq!(input.get_axis("move_left", "move_right"), -0.5f32);
+
+        input.set_action_strength("move_right", 0f32);
+        assert!(!input.is_action_pressed("move_right"));
+    }
+
+    #[test]
+    fn clicks_and_text_are_seen_for_one_frame() {
+        let (mut harness, log) = harness();
+        harness.push(InputLogState::new("A", &log));
+
+        input(&mut harness).press_mouse_button(MOUSE_BUTTON_LEFT);
+        input(&mut harness).push_text("hi");
+        input(&mut harness).set_mouse_position(Vector2::new(3f32, 4f32));
+        harness.step_n(2);
+
+        assert_eq!(take(&log), vec!["A click", "A typed hi"]);
+        assert_eq!(input(&mut harness).mouse_position(), Vector2::new(3f32, 4f32));
+        assert_eq!(input(&mut harness).text(), "");
+    }
+
+    #[test]
+    fn consumed_input_is_hidden_from_the_states_below() {
+        let (mut harness, log) = harness();
+        harness.push(InputLogState::new("A", &log)).push(InputLogState::new("B", &log).consuming());
+
+        input(&mut harness).press_mouse_button(MOUSE_BUTTON_LEFT);
+        input(&mut harness).push_text("hi");
+        harness.step();
+        assert_eq!(take(&log), vec!["B click", "B typed hi", "A typed hi"]);
+        assert!(!input(&mut harness).is_mouse_button_consumed(MOUSE_BUTTON_LEFT));
+    }
+
+    #[test]
+    fn consumed_input_is_hidden_from_lower_layers() {
+        let (mut harness, log) = harness();
+        harness.engine_mut().add_layer("ui");
+        harness.push(InputLogState::new("A", &log));
+        harness.engine_mut().select_layer("ui");
+        harness.push(InputLogState::new("Hud", &log).consuming());
+
+        input(&mut harness).press_mouse_button(MOUSE_BUTTON_LEFT);
+        harness.step();
+        assert_eq!(take(&log), vec!["Hud click"]);
+    }
+
+    #[test]
+    fn blocking_states_hide_all_input_from_the_states_below() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(InputLogState::new("Menu", &log).blocking());
+        take(&log);
+
+        input(&mut harness).press_mouse_button(MOUSE_BUTTON_LEFT);
+        input(&mut harness).push_text("hi");
+        harness.tap_action("jump");
+        assert_eq!(take(&log), vec!["Menu click", "Menu typed hi"]);
+
+        send(&mut harness, || Trans::Pop);
+        harness.step();
+        harness.tap_action("jump");
+        assert_eq!(take(&log), vec!["A uncover", "A jump"]);
+    }
+}
\ No newline at end of file
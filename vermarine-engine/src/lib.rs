@@ -49,8 +49,23 @@
 //! fn _physics_process(&mut self, owner: Node, delta: f64) {
 //!    self.engine._physics_process(owner, delta);
 //! }
+//! 
+//! // Window close requests are passed to the engine so that our states can handle them
+//! #[export]
+//! fn _notification(&mut self, owner: Node, what: i64) {
+//!     self.engine._notification(owner, what);
+//! }
+//! 
+//! // Mouse and text input reach the InputState resource through _input
+//! #[export]
+//! fn _input(&mut self, owner: Node, event: Option<InputEvent>) {
+//!     self.engine._input(owner, event);
+//! }
 //! ```
 //! 
+//! -- NOTE: The engine stops godot from closing the window by itself so that states can intercept close requests,
+//! if _notification is not hooked up the window will not close when the close button is pressed.
+//! 
 //! ### Getting started with your first state
 //! Adding your first state to the stack is a bit unique as it's the only time you have to do it from outside of a system or state (More on this later)
 //! The first thing to do is create a struct for us to implement the state trait on
@@ -79,13 +94,33 @@
 //! 
 //! 3.) on_push - This method gets called on a state when it is pushed onto the stack
 //! 
-//! 4.) on_uncover - This method gets called on a state when the state ontop of it in the stack is popped
+//! 4.) on_uncover - This method gets called on a state when the state ontop of it in the stack is popped,
+//! if the state ontop was popped with Trans::PopWith then on_uncover_with gets called instead with the value it was popped with
 //! 
 //! 5.) on_pop - This method gets called on a state when it is popped off of the stack
 //! 
 //! 6.) get_name - This method is used in various debug information to identify a state and has no functional effect on the running of your state
 //! 
-//! 7.) is_node - This method is slightly more complicated to explain and deserves its own section so we'll only give a brief explanation here.
+//! 7.) on_quit - This method gets called on every state from the top of the stack down when the engine quits, right before on_pop, this is where you should flush any saves
+//! 
+//! 8.) on_quit_request - This method gets called on the state at the top of the stack when the window is asked to close, it returns the Trans to run.
+//! By default this is Trans::Quit but you can return a Trans::Push of an "are you sure" state instead
+//! 
+//! 9.) schedule and shadow_schedule - These methods return the systems the engine should run on the state's world after update and after shadow_update,
+//! they are called once when the state is pushed and the engine owns and runs the schedules from then on
+//! 
+//! 10.) cover_policy - This method decides whether the states below this one are hidden, frozen (no shadow_update) or keep running while this state is on the stack
+//! 
+//! 11.) sync_when_covered - Every visible state is synced to godot each frame, return false from this method to only sync this state while it is at the top of the stack
+//! 
+//! 12.) blocks_input - Return true from this method to stop the states below this one from seeing any input, states are updated from the top down so modal menus can't leak input to the game
+//! 
+//! 13.) on_load_error - This method gets called on the loading state of a Trans::LoadThen when one of its models fails to load, it returns the Trans to run.
+//! By default this pops the loading state
+//! 
+//! 14.) on_transfer_in - This method gets called after entities have been moved or cloned into this state with Trans::Transfer or Trans::SwitchCarrying
+//! 
+//! 15.) is_node - This method is slightly more complicated to explain and deserves its own section so we'll only give a brief explanation here.
 //! is_node() is used to specify a Models\<T> to instance alongside the state, it can be accessed via data.statenode (for example usage see BaseState's shadow_update and is_node methods in scene-example).
 //! 
 //! If you try to run your project now you'll probably find that nothing happens!
@@ -236,9 +271,11 @@
 //! 
 //! Renderables: this component stores a tree like structure of renderables from the Models\<T> resource along with positions to render each renderable at. 
 //! The positions specified are relative to the parent's position. In the case of the root level renderable its position is relative to the Position component attached to the entity.
+//! Children are added with Renderable::with_child(key, child) and found again with renderable.child(key), where the key is a string, a number or your own enum.
 //! 
 //! Position is fairly self explanatory but nevertheless, position stores the position of our entity and is what is used by the engine to determine the position of an entity.
 //! This component is unnecessary for drawing to the screen as if it's not found the renderables assume the entity is at 0,0.
+//! Position also holds the rotation, scale, skew, pivot and z_index of the entity, these can be set with Position::new(x, y).with_scale(2f32, 2f32) and so on.
 //! 
 //! 8...  That's us done! If you play the game you'll see your square.tscn at whatever position specified in the Position component
 //! 
@@ -263,13 +300,14 @@
 //! 3. Next lets load our scene into our Models\<T> resource like we did with square, add this code to your _init() method
 //! ```models.insert(Some("Pause"), None, load_scene("pause"), Template::Scene);```
 //! 
-//! 4. Inside update make YourState check if the pause key was pressed and then send a Push transition to push PauseState onto the stack
+//! 4. Inside update make YourState check if the pause key was pressed and then send a Push transition to push PauseState onto the stack.
+//! The engine captures which actions are pressed every frame into the InputState resource so that's where we'll check for the pause key
 //! ```
-//! if Input::godot_singleton().is_action_just_pressed(GodotString::from("pause")) {
+//! if resources.get::<InputState>().unwrap().is_action_just_pressed("pause") {
 //!     // Get the TransResource that allows us to send state transitions to the engine
 //!     let sender = resources.get::<TransResource>().unwrap();
 //!     // Send a closure that creates the Trans we want to execute
-//!     sender.trans.try_send(Box::from(|| Trans::Push(Box::new(PauseState { }))).ok();
+//!     sender.send(|| Trans::Push(Box::new(PauseState { }))).unwrap();
 //! }
 //! ```
 //! 
@@ -290,11 +328,11 @@
 //! 
 //! 6. In update check if "pause" key is pressed and send a Pop transition if it is
 //! ```
-//! if Input::godot_singleton().is_action_just_pressed(GodotString::from("pause")) {
+//! if resources.get::<InputState>().unwrap().is_action_just_pressed("pause") {
 //!     // Get the TransResource that allows us to send state transitions to the engine
 //!     let sender = resources.get::<TransResource>().unwrap();
 //!     // Send a closure that creates the Trans we want to execute
-//!     sender.trans.try_send(Box::from(|| Trans::Pop)).ok();
+//!     sender.send(|| Trans::Pop).unwrap();
 //! }
 //! ```
 //! 
@@ -317,21 +355,77 @@
 //! ```
 //! 
 //! If you run this now you should find that if you press pause the counter will stop printing out, the pause text will appear and if you hit pause again it will resume.
+//! 
+//! ### Testing your states without godot
+//! 
+//! VermarineEngine::headless() creates an engine that records a fake node tree instead of creating godot nodes, this means that states and systems
+//! can be run from cargo test. The TestHarness wraps a headless engine and lets you step frames, press input actions and look at the worlds and node tree.
+//! 
+//! -- NOTE: Anything that calls into godot directly (load_scene(), Input::godot_singleton(), godot_print!() etc.) will not work when running headless,
+//! register your models with Models.insert_headless() and read input from the InputState resource instead.
+//! ```
+//! #[test]
+//! fn pause_pushes_pause_state() {
+//!     let mut models = Models::<i32>::default();
+//!     models.insert_headless(Some("Pause"), None, Template::Scene);
+//! 
+//!     let mut harness = TestHarness::new(models);
+//!     harness.push(Box::new(PauseState { }));
+//!     harness.step();
+//! 
+//!     // The pause text entity should have had a node created for it
+//!     assert_eq!(harness.renderable_count(0), 1);
+//! 
+//!     // Pressing pause should pop PauseState off of the stack
+//!     harness.tap_action("pause");
+//!     assert!(harness.engine().state_data(0).is_none());
+//! }
+//! ```
 
 mod engine;
+mod backend;
+mod harness;
+mod input;
+mod bindings;
+mod axes;
+mod loading;
+mod time;
+mod transfer;
 mod components;
+mod hierarchy;
 mod models;
+mod replay;
 mod state;
 
 pub use crate::engine::*;
+pub use crate::backend::*;
+pub use crate::harness::*;
+pub use crate::input::*;
+pub use crate::bindings::*;
+pub use crate::axes::*;
+pub use crate::loading::*;
+pub use crate::time::*;
+pub use crate::transfer::*;
 pub use crate::components::*;
+pub use crate::hierarchy::*;
 pub use crate::models::*;
+pub use crate::replay::*;
 pub use crate::state::*;
 
 pub mod prelude {
     pub use crate::engine::*;
+    pub use crate::backend::*;
+    pub use crate::harness::*;
+    pub use crate::input::*;
+    pub use crate::bindings::*;
+    pub use crate::axes::*;
+    pub use crate::loading::*;
+    pub use crate::time::*;
+    pub use crate::transfer::*;
     pub use crate::components::*;
+    pub use crate::hierarchy::*;
     pub use crate::models::*;
+    pub use crate::replay::*;
     pub use crate::state::*;
     pub use rand;
     pub use rand::Rng;
@@ -0,0 +1,334 @@
+use crate::prelude::*;
+use std::any::Any;
+use std::collections::VecDeque;
+
+/// A model to load in the background with Trans::LoadThen, once loaded it gets inserted into Models\<T> the same way Models.insert() does
+/// ```
+/// let models: Vec<ModelLoad<Renderables>> = vec![
+///     ModelLoad::new("Player", Template::ASprite(AnimSprite::default()))
+///         .with_alias("Player")
+///         .with_key(Renderables::Creatures(CreatureRenderables::Player)),
+///     ModelLoad::new("levels/forest", Template::Scene)
+///         .with_alias("Forest"),
+/// ];
+/// sender.send(move || Trans::LoadThen(Box::new(LoadingState { }), models.into(), Box::new(Trans::Replace(Box::new(LevelState { }))))).unwrap();
+/// ```
+pub struct ModelLoad<T> {
+    pub path: String,
+    pub template: Template,
+    pub alias: Option<&'static str>,
+    pub t_key: Option<T>,
+}
+
+impl<T> ModelLoad<T> {
+    /// Takes a path the same way load_scene() does, the path gets prepended with res://scenes/ and appended with .tscn
+    pub fn new(path: &str, template: Template) -> Self {
+        ModelLoad {
+            path: path.into(),
+            template,
+            alias: None,
+            t_key: None,
+        }
+    }
+
+    pub fn with_alias(mut self, alias: &'static str) -> Self {
+        self.alias = Some(alias);
+        self
+    }
+
+    pub fn with_key(mut self, t_key: T) -> Self {
+        self.t_key = Some(t_key);
+        self
+    }
+}
+
+/// The models of a Trans::LoadThen, this is made from a Vec\<ModelLoad\<T>> with into().
+/// The T has to be the same as the T in VermarineEngine\<T>, if it isn't the Trans::LoadThen is rejected with TransError::WrongKeyType
+pub struct ModelLoads {
+    models: Box<dyn Any>,
+    len: usize,
+}
+
+impl ModelLoads {
+    /// The number of models to load
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn downcast<T>(self) -> Option<Vec<ModelLoad<T>>>
+        where T: 'static {
+        self.models.downcast::<Vec<ModelLoad<T>>>().ok().map(|models| *models)
+    }
+}
+
+impl<T> From<Vec<ModelLoad<T>>> for ModelLoads
+    where T: 'static {
+    fn from(models: Vec<ModelLoad<T>>) -> Self {
+        ModelLoads {
+            len: models.len(),
+            models: Box::new(models),
+        }
+    }
+}
+
+/// An error from Trans::LoadThen, this gets passed to the on_load_error method of the loading state
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadError {
+    pub path: String,
+    pub message: String,
+}
+
+/// The result of polling a load that was started with SceneBackend::start_load
+pub enum LoadStatus {
+    /// The load is still going, holds how far through the load is from 0 to 1
+    Loading(f32),
+    /// The load finished, the scene is None when running on the HeadlessBackend
+    Loaded(Option<PackedScene>),
+    Failed(String),
+}
+
+/// The LoadingProgress resource is inserted when a Trans::LoadThen is run and is updated every frame while the models load
+/// ```
+/// fn update(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     let progress = resources.get::<LoadingProgress>().unwrap();
+///     godot_print!("Loading {}%", (progress.progress() * 100f32) as i32);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadingProgress {
+    pub(crate) progress: f32,
+    pub(crate) loaded: usize,
+    pub(crate) total: usize,
+    pub(crate) current: Option<String>,
+    pub(crate) errors: Vec<LoadError>,
+}
+
+impl LoadingProgress {
+    /// How far through loading all of the models the load is from 0 to 1
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// The number of models that have finished loading
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    /// The number of models being loaded
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The path of the model currently being loaded
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|path| path.as_str())
+    }
+
+    pub fn errors(&self) -> &Vec<LoadError> {
+        &self.errors
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.loaded == self.total
+    }
+}
+
+/// A Trans::LoadThen that is in progress
+pub(crate) struct ActiveLoad<T> {
+    /// The index of the layer the loading state was pushed onto
+    pub(crate) layer: usize,
+    /// The stack index of the loading state
+    pub(crate) state_index: usize,
+    pub(crate) pending: VecDeque<ModelLoad<T>>,
+    pub(crate) current: Option<(ModelLoad<T>, usize)>,
+    pub(crate) current_progress: f32,
+    pub(crate) loaded: Vec<(ModelLoad<T>, Option<PackedScene>)>,
+    pub(crate) total: usize,
+    pub(crate) then: Trans,
+}
+
+impl<T> ActiveLoad<T> {
+    pub(crate) fn new(layer: usize, state_index: usize, models: Vec<ModelLoad<T>>, then: Trans) -> Self {
+        ActiveLoad {
+            layer,
+            state_index,
+            total: models.len(),
+            pending: models.into_iter().collect(),
+            current: None,
+            current_progress: 0f32,
+            loaded: vec![],
+            then,
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.current.is_none() && self.pending.is_empty()
+    }
+
+    pub(crate) fn progress(&self) -> LoadingProgress {
+        let progress = if self.total == 0 {
+            1f32
+        } else {
+            (self.loaded.len() as f32 + self.current_progress) / self.total as f32
+        };
+
+        LoadingProgress {
+            progress,
+            loaded: self.loaded.len(),
+            total: self.total,
+            current: self.current.as_ref().map(|(model, _)| model.path.clone()),
+            errors: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::harness::tests::*;
+
+    /// Records its pushes, pops and errors, on an error it runs retry if it has one and pops itself otherwise
+    struct LoadingState {
+        log: Log,
+        retry: Option<Box<dyn FnOnce() -> Trans>>,
+    }
+
+    impl State for LoadingState {
+        fn on_push(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.log.borrow_mut().push("Loading push".into()) }
+        fn on_pop(&mut self, _data: &mut StateData, _resources: &mut Resources) { self.log.borrow_mut().push("Loading pop".into()) }
+        fn on_load_error(&mut self, _data: &mut StateData, _resources: &mut Resources, error: &LoadError) -> Trans {
+            self.log.borrow_mut().push(format!("Loading error {}", error.path));
+            match self.retry.take() {
+                Some(retry) => retry(),
+                None => Trans::Pop,
+            }
+        }
+        fn get_name(&mut self, _data: &mut StateData, _resources: &mut Resources) -> String { "Loading".into() }
+    }
+
+    fn loading(log: &Log) -> Box<LoadingState> {
+        Box::new(LoadingState { log: log.clone(), retry: None })
+    }
+
+    fn load_player(log: &Log) -> Trans {
+        let models = vec![ModelLoad::new("Player", Template::None).with_alias("Player").with_key(7)];
+        Trans::LoadThen(loading(log), models.into(), Box::new(Trans::Push(LogState::new("B", log))))
+    }
+
+    fn load_path(log: &Log, path: &'static str) -> Trans {
+        let models: Vec<ModelLoad<i32>> = vec![ModelLoad::new(path, Template::None).with_alias(path)];
+        Trans::LoadThen(loading(log), models.into(), Box::new(Trans::Push(LogState::new("B", log))))
+    }
+
+    fn errors(harness: &mut TestHarness<i32>) -> Vec<LoadError> {
+        harness.resources().get::<LoadingProgress>().unwrap().errors().clone()
+    }
+
+    #[test]
+    fn loaded_models_are_inserted_before_the_trans_runs() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        take(&log);
+
+        let trans = load_player(&log);
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B"]);
+        assert_eq!(take(&log), vec!["A cover", "Loading push", "Loading pop", "A uncover", "A cover", "B push"]);
+
+        let models = harness.resources().get::<Models<i32>>().unwrap();
+        assert!(models.index_from_alias("Player").is_some());
+        assert_eq!(models.index_from_t(&7), models.index_from_alias("Player"));
+    }
+
+    #[test]
+    fn a_load_while_another_is_running_is_rejected_before_pushing() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.tree_mut().hold_load("Slow");
+
+        let trans = load_path(&log, "Slow");
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "Loading"]);
+        assert_eq!(harness.resources().get::<LoadingProgress>().unwrap().progress(), 0.5f32);
+
+        let trans = load_player(&log);
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "Loading"]);
+        let rejected = harness.resources().get::<TransResource>().unwrap().rejected().to_vec();
+        assert_eq!(rejected[0].error, TransError::AlreadyLoading);
+
+        harness.tree_mut().release_load("Slow");
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn a_failed_load_pops_the_loading_state_by_default() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.tree_mut().set_missing("Missing");
+        take(&log);
+
+        let trans = load_path(&log, "Missing");
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(take(&log), vec!["A cover", "Loading push", "Loading error Missing", "Loading pop", "A uncover"]);
+        assert_eq!(errors(&mut harness)[0].path, "Missing");
+
+        // The failed load no longer blocks new loads
+        let trans = load_player(&log);
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn the_loading_state_can_retry_a_failed_load() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.tree_mut().set_missing("Missing");
+
+        let retry_log = log.clone();
+        let mut state = loading(&log);
+        state.retry = Some(Box::new(move || Trans::Sequence(vec![Trans::Pop, load_player(&retry_log)])));
+        let models: Vec<ModelLoad<i32>> = vec![ModelLoad::new("Missing", Template::None).with_alias("Missing")];
+        send(&mut harness, move || Trans::LoadThen(state, models.into(), Box::new(Trans::None)));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A", "B"]);
+    }
+
+    #[test]
+    fn a_missing_models_resource_is_a_load_error() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.resources().remove::<Models<i32>>();
+
+        let trans = load_player(&log);
+        send(&mut harness, move || trans);
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        assert_eq!(errors(&mut harness).len(), 1);
+    }
+
+    #[test]
+    fn keys_of_the_wrong_type_are_rejected() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+
+        let models = vec![ModelLoad::new("Player", Template::None).with_key("Player")];
+        let state = loading(&log);
+        send(&mut harness, move || Trans::LoadThen(state, models.into(), Box::new(Trans::None)));
+        harness.step();
+        assert_eq!(harness.engine().state_names(), vec!["A"]);
+        let rejected = harness.resources().get::<TransResource>().unwrap().rejected().to_vec();
+        assert_eq!(rejected[0].error, TransError::WrongKeyType);
+    }
+}
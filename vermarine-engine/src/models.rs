@@ -3,7 +3,8 @@ use std::collections::HashMap;
 
 pub struct Models<T> 
     where T: Eq + std::hash::Hash { 
-    data: Vec<(PackedScene, Template, usize)>,
+    data: Vec<(Option<PackedScene>, Template, usize)>,
+    spatial: Vec<bool>,
     name_data_lookup: HashMap<&'static str, usize>,
     t_data_lookup: HashMap<T, usize>,
 }
@@ -16,6 +17,7 @@ impl<T> Default for Models<T>
     fn default() -> Self {
         Models::<T> {
             data: vec![],
+            spatial: vec![],
             name_data_lookup: HashMap::new(),
             t_data_lookup: HashMap::new(),
         }
@@ -61,12 +63,36 @@ impl<T> Models<T>
 
     pub(crate) fn scene_from_index(&self, index: usize) -> Option<&PackedScene> {
         if let Some(data) = self.data.get(index) {
-            return Some(&data.0);
+            return data.0.as_ref();
         }
         None
     }
 
+    /// Returns true if the root node of the model is a Spatial, renderables using it get Spatial containers instead of Node2D ones
+    pub fn is_spatial(&self, index: usize) -> bool {
+        self.spatial.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Overrides whether the model is treated as a 3D model, this is needed for models inserted with insert_headless() as they have no scene to check
+    pub fn set_spatial(&mut self, index: usize, spatial: bool) {
+        if let Some(value) = self.spatial.get_mut(index) {
+            *value = spatial;
+        }
+    }
+
     pub fn insert(&mut self, alias: Option<&'static str>, t_key: Option<T>, scene: PackedScene, template: Template) -> Option<usize> {
+        self.insert_scene(alias, t_key, Some(scene), template)
+    }
+
+    /// Inserts a model without a godot scene, this is how models get registered when running the engine with a HeadlessBackend
+    /// as load_scene() cannot be called without godot.
+    /// 
+    /// The HeadlessBackend records which model index each fake node was instanced from so that tests can check what was spawned.
+    pub fn insert_headless(&mut self, alias: Option<&'static str>, t_key: Option<T>, template: Template) -> Option<usize> {
+        self.insert_scene(alias, t_key, None, template)
+    }
+
+    pub(crate) fn insert_scene(&mut self, alias: Option<&'static str>, t_key: Option<T>, scene: Option<PackedScene>, template: Template) -> Option<usize> {
         let index = self.data.len();
         let mut has_valid_key = false;
 
@@ -80,6 +106,7 @@ impl<T> Models<T>
         }
 
         if has_valid_key {
+            self.spatial.push(scene.as_ref().map_or(false, |scene| is_spatial_scene(scene)));
             self.data.push((scene, template, index));
             return Some(index);
         }
@@ -87,6 +114,18 @@ impl<T> Models<T>
     }
 }
 
+/// Checks the class of the scene's root node without instancing it
+fn is_spatial_scene(scene: &PackedScene) -> bool {
+    unsafe {
+        match scene.get_state() {
+            Some(state) if state.get_node_count() > 0 => {
+                ClassDB::godot_singleton().is_parent_class(state.get_node_type(0), GodotString::from_str("Spatial"))
+            },
+            _ => false,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Template {
     None,
@@ -0,0 +1,433 @@
+use crate::prelude::*;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::io::{Error, ErrorKind};
+
+/// The Random resource is a seeded random number generator, systems should use it instead of rand::thread_rng() so that replays play out the same way
+/// ```
+/// SystemBuilder::<()>::new("ExampleSystem")
+///     .write_resource::<Random>()
+///     .build(move |commands, world, random, queries| {
+///         let anim = random.gen_range(0, 3);
+///         // --snip
+///     })
+/// ```
+pub struct Random {
+    rng: StdRng,
+    seed: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Random {
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+        }
+    }
+
+    /// The seed that the generator was last seeded with
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = seed;
+    }
+}
+
+impl std::ops::Deref for Random {
+    type Target = StdRng;
+
+    fn deref(&self) -> &StdRng {
+        &self.rng
+    }
+}
+
+impl std::ops::DerefMut for Random {
+    fn deref_mut(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+}
+
+/// The raw input of a single frame, this ignores any input consumed by states
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputSnapshot {
+    /// Every pressed action along with its strength
+    pub actions: Vec<(String, f32)>,
+    pub mouse_position: Vector2,
+    pub mouse_buttons: Vec<i64>,
+    pub text: String,
+    /// The device, axis and value of every gamepad axis
+    pub joy_axes: Vec<(i64, i64, f32)>,
+    /// Where the first touch started and where it is now
+    pub touch: Option<(Vector2, Vector2)>,
+    /// The device ids of every connected gamepad
+    pub joypads: Vec<i64>,
+    /// Gamepads that were plugged in or unplugged this frame
+    pub joypad_events: Vec<JoypadEvent>,
+    /// The binding pressed most recently this frame
+    pub last_pressed: Option<Binding>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+    /// The delta passed to the engine before the time scale is applied
+    pub delta: f64,
+    pub input: InputSnapshot,
+}
+
+/// Every frame of input recorded by the engine along with the seed of the Random resource,
+/// replaying it with VermarineEngine::start_replay() runs the game the exact same way as long as all randomness comes from Random.
+///
+/// Recordings are saved as text with one line per action, mouse button and so on, so action names can't contain whitespace.
+/// ```
+/// let recording = engine.stop_recording().unwrap();
+/// recording.save("bug-1234.rec").unwrap();
+///
+/// // --snip
+///
+/// let recording = InputRecording::load("bug-1234.rec").unwrap();
+/// engine.start_replay(recording);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputRecording {
+    pub seed: u64,
+    pub frames: Vec<RecordedFrame>,
+}
+
+const RECORDING_HEADER: &str = "vermarine-recording 1";
+
+impl InputRecording {
+    pub fn new(seed: u64) -> Self {
+        InputRecording {
+            seed,
+            frames: vec![],
+        }
+    }
+
+    /// # Errors
+    ///
+    /// This returns an error if the file could not be written to
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    /// # Errors
+    ///
+    /// This returns an error if the file could not be read or is not a recording
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        InputRecording::from_text(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut output = InputRecording::header_text(self.seed);
+        for frame in self.frames.iter() {
+            output.push_str(&InputRecording::frame_text(frame));
+        }
+        output
+    }
+
+    /// The start of a recording's text, the text of each frame follows it
+    pub(crate) fn header_text(seed: u64) -> String {
+        format!("{}\nseed {}\n", RECORDING_HEADER, seed)
+    }
+
+    pub(crate) fn frame_text(frame: &RecordedFrame) -> String {
+        let mut output = format!("frame {}\n", frame.delta);
+        for (action, strength) in frame.input.actions.iter() {
+            output.push_str(&format!("action {} {}\n", action, strength));
+        }
+        output.push_str(&format!("mouse {} {}\n", frame.input.mouse_position.x, frame.input.mouse_position.y));
+        for button in frame.input.mouse_buttons.iter() {
+            output.push_str(&format!("button {}\n", button));
+        }
+        for (device, axis, value) in frame.input.joy_axes.iter() {
+            output.push_str(&format!("joy {} {} {}\n", device, axis, value));
+        }
+        if let Some((start, current)) = frame.input.touch {
+            output.push_str(&format!("touch {} {} {} {}\n", start.x, start.y, current.x, current.y));
+        }
+        if !frame.input.text.is_empty() {
+            let chars: Vec<String> = frame.input.text.chars().map(|c| (c as u32).to_string()).collect();
+            output.push_str(&format!("text {}\n", chars.join(" ")));
+        }
+        for device in frame.input.joypads.iter() {
+            output.push_str(&format!("joypad {}\n", device));
+        }
+        for event in frame.input.joypad_events.iter() {
+            match event {
+                JoypadEvent::Connected(device) => output.push_str(&format!("connected {}\n", device)),
+                JoypadEvent::Disconnected(device) => output.push_str(&format!("disconnected {}\n", device)),
+            }
+        }
+        if let Some(binding) = frame.input.last_pressed {
+            output.push_str(&format!("pressed {}\n", binding));
+        }
+        output
+    }
+
+    /// # Errors
+    ///
+    /// This returns an error with ErrorKind::InvalidData if the text is not a recording
+    pub fn from_text(text: &str) -> std::io::Result<Self> {
+        fn invalid(line: usize, message: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, format!("Line {} of the recording {}", line + 1, message))
+        }
+
+        fn parse<F: std::str::FromStr>(line: usize, value: Option<&str>) -> std::io::Result<F> {
+            value.and_then(|value| value.parse().ok()).ok_or_else(|| invalid(line, "has a missing or invalid value"))
+        }
+
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, RECORDING_HEADER)) => {},
+            _ => return Err(invalid(0, "is not a vermarine recording header")),
+        }
+
+        let seed = match lines.next() {
+            Some((i, line)) if line.starts_with("seed ") => parse(i, line.split_whitespace().nth(1))?,
+            _ => return Err(invalid(1, "is not a seed")),
+        };
+
+        let mut recording = InputRecording::new(seed);
+        for (i, line) in lines {
+            let mut values = line.split_whitespace();
+            let kind = match values.next() {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            if kind == "frame" {
+                recording.frames.push(RecordedFrame {
+                    delta: parse(i, values.next())?,
+                    input: InputSnapshot::default(),
+                });
+                continue;
+            }
+
+            let frame = match recording.frames.last_mut() {
+                Some(frame) => &mut frame.input,
+                None => return Err(invalid(i, "comes before the first frame")),
+            };
+            match kind {
+                "action" => {
+                    let action = values.next().ok_or_else(|| invalid(i, "is missing an action name"))?;
+                    frame.actions.push((action.into(), parse(i, values.next())?));
+                },
+                "mouse" => frame.mouse_position = Vector2::new(parse(i, values.next())?, parse(i, values.next())?),
+                "button" => frame.mouse_buttons.push(parse(i, values.next())?),
+                "joy" => frame.joy_axes.push((parse(i, values.next())?, parse(i, values.next())?, parse(i, values.next())?)),
+                "touch" => {
+                    let start = Vector2::new(parse(i, values.next())?, parse(i, values.next())?);
+                    let current = Vector2::new(parse(i, values.next())?, parse(i, values.next())?);
+                    frame.touch = Some((start, current));
+                },
+                "joypad" => frame.joypads.push(parse(i, values.next())?),
+                "connected" => frame.joypad_events.push(JoypadEvent::Connected(parse(i, values.next())?)),
+                "disconnected" => frame.joypad_events.push(JoypadEvent::Disconnected(parse(i, values.next())?)),
+                "pressed" => frame.last_pressed = Some(parse(i, values.next())?),
+                "text" => {
+                    for value in values {
+                        let c = std::char::from_u32(parse(i, Some(value))?).ok_or_else(|| invalid(i, "has an invalid character"))?;
+                        frame.text.push(c);
+                    }
+                },
+                _ => return Err(invalid(i, &format!("has an unknown entry {}", kind))),
+            }
+        }
+        Ok(recording)
+    }
+}
+
+impl Default for InputSnapshot {
+    fn default() -> Self {
+        InputSnapshot {
+            actions: vec![],
+            mouse_position: Vector2::new(0f32, 0f32),
+            mouse_buttons: vec![],
+            text: String::new(),
+            joy_axes: vec![],
+            touch: None,
+            joypads: vec![],
+            joypad_events: vec![],
+            last_pressed: None,
+        }
+    }
+}
+
+/// A recording in progress, with record_to() each frame is also written to the file as soon as it is recorded
+pub(crate) struct ActiveRecording {
+    pub(crate) recording: InputRecording,
+    pub(crate) file: Option<(String, std::fs::File)>,
+}
+
+impl ActiveRecording {
+    /// Adds the frame to the recording and writes it to the end of the file, if the file can't be written to it is closed and an error is returned
+    pub(crate) fn push(&mut self, frame: RecordedFrame) -> Result<(), String> {
+        use std::io::Write;
+
+        let result = match self.file.as_mut() {
+            Some((path, file)) => file.write_all(InputRecording::frame_text(&frame).as_bytes())
+                .map_err(|error| format!("Could not write the recording to {}: {}", path, error)),
+            None => Ok(()),
+        };
+        if result.is_err() {
+            self.file = None;
+        }
+        self.recording.frames.push(frame);
+        result
+    }
+}
+
+/// A replay in progress
+pub(crate) struct ActiveReplay {
+    pub(crate) recording: InputRecording,
+    pub(crate) cursor: usize,
+}
+
+impl ActiveReplay {
+    pub(crate) fn next_frame(&mut self) -> Option<&RecordedFrame> {
+        let frame = self.recording.frames.get(self.cursor);
+        self.cursor += 1;
+        frame
+    }
+}
+
+/// How a game should be started, this is read from the command line or the environment by VermarineEngine::configure_from_env()
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionMode {
+    /// Input comes from the player
+    Live,
+    /// Input comes from the player and is recorded to the path as it is played
+    Record(String),
+    /// Input comes from the recording at the path
+    Replay(String),
+}
+
+impl SessionMode {
+    /// Reads `--record <path>` or `--replay <path>` from the command line,
+    /// or the VERMARINE_RECORD or VERMARINE_REPLAY environment variables if neither argument was passed
+    pub fn from_env() -> Self {
+        let args: Vec<String> = std::env::args().collect();
+        for (i, arg) in args.iter().enumerate() {
+            match (arg.as_str(), args.get(i + 1)) {
+                ("--record", Some(path)) => return SessionMode::Record(path.clone()),
+                ("--replay", Some(path)) => return SessionMode::Replay(path.clone()),
+                _ => {},
+            }
+        }
+
+        if let Ok(path) = std::env::var("VERMARINE_REPLAY") {
+            return SessionMode::Replay(path);
+        }
+        if let Ok(path) = std::env::var("VERMARINE_RECORD") {
+            return SessionMode::Record(path);
+        }
+        SessionMode::Live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::tests::*;
+
+    fn recording() -> InputRecording {
+        let mut input = InputState::default();
+        input.press("jump");
+        input.set_action_strength("jump", 0.5f32);
+        input.press_mouse_button(MOUSE_BUTTON_LEFT);
+        input.push_text("hi there");
+        input.set_connected_joypads(vec![0]);
+        input.set_joy_axis(0, 1, -0.25f32);
+        input.begin_touch(Vector2::new(1f32, 2f32));
+        input.drag_touch(Vector2::new(3f32, 4f32));
+        input.set_last_pressed(Binding::JoyAxis(1, false));
+
+        let mut recording = InputRecording::new(42);
+        recording.frames.push(RecordedFrame { delta: 1f64 / 60f64, input: input.snapshot() });
+        recording.frames.push(RecordedFrame { delta: 0.5f64, input: InputSnapshot::default() });
+        recording
+    }
+
+    #[test]
+    fn recordings_round_trip_through_text() {
+        let recording = recording();
+        assert_eq!(InputRecording::from_text(&recording.to_text()).unwrap(), recording);
+    }
+
+    #[test]
+    fn snapshots_cover_joypads_and_the_last_pressed_binding() {
+        let snapshot = &recording().frames[0].input;
+        assert_eq!(snapshot.joypads, vec![0]);
+        assert_eq!(snapshot.joypad_events, vec![JoypadEvent::Connected(0)]);
+        assert_eq!(snapshot.last_pressed, Some(Binding::JoyAxis(1, false)));
+
+        let mut replayed = InputState::default();
+        replayed.set_connected_joypads(vec![3]);
+        replayed.apply_snapshot(snapshot);
+        assert_eq!(&replayed.snapshot(), snapshot);
+        assert_eq!(replayed.connected_joypads(), &[0]);
+        assert_eq!(replayed.last_pressed(), Some(Binding::JoyAxis(1, false)));
+    }
+
+    #[test]
+    fn text_that_isnt_a_recording_is_rejected() {
+        assert_eq!(InputRecording::from_text("seed 1").unwrap_err().kind(), ErrorKind::InvalidData);
+        let missing_frame = format!("{}\nseed 1\naction jump 1\n", RECORDING_HEADER);
+        assert!(InputRecording::from_text(&missing_frame).is_err());
+        let unknown = format!("{}\nseed 1\nframe 0.1\nwarp 1\n", RECORDING_HEADER);
+        assert!(InputRecording::from_text(&unknown).is_err());
+    }
+
+    #[test]
+    fn active_recordings_write_each_frame_as_it_is_pushed() {
+        let name = format!("vermarine-{}-active_recordings_write_each_frame_as_it_is_pushed.rec", std::process::id());
+        let path = std::env::temp_dir().join(name).to_string_lossy().into_owned();
+        let mut file = std::fs::File::create(&path).unwrap();
+        std::io::Write::write_all(&mut file, InputRecording::header_text(42).as_bytes()).unwrap();
+
+        let mut active = ActiveRecording { recording: InputRecording::new(42), file: Some((path.clone(), file)) };
+        for frame in recording().frames {
+            active.push(frame).unwrap();
+        }
+        drop(active);
+        let loaded = InputRecording::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), recording());
+    }
+
+    /// Rolls a random number each time jump is pressed
+    struct RollState(Log);
+
+    impl State for RollState {
+        fn update(&mut self, _data: &mut StateData, resources: &mut Resources) {
+            if resources.get::<InputState>().unwrap().is_action_just_pressed("jump") {
+                let roll = resources.get_mut::<Random>().unwrap().gen_range(0u32, 1000u32);
+                self.0.borrow_mut().push(format!("jump {}", roll));
+            }
+        }
+    }
+
+    fn roll_harness() -> (TestHarness<i32>, Log) {
+        let (mut harness, log) = harness();
+        harness.push(Box::new(RollState(log.clone())));
+        (harness, log)
+    }
+
+    #[test]
+    fn replays_play_out_the_same_as_the_recording() {
+        let (mut harness, log) = roll_harness();
+        harness.engine_mut().start_recording();
+        harness.tap_action("jump").step().tap_action("jump");
+        let recording = harness.engine_mut().stop_recording().unwrap();
+        let recorded = take(&log);
+        assert_eq!(recorded.len(), 2);
+
+        let (mut harness, log) = roll_harness();
+        harness.engine_mut().start_replay(recording);
+        harness.step_n(3);
+        assert_eq!(take(&log), recorded);
+    }
+}
@@ -1,6 +1,8 @@
 use std::fmt::{Debug, Formatter, Result as FmtResult};
 use crate::prelude::*;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
+use legion::systems::resource::{Fetch, FetchMut};
 
 pub enum Trans {
     /// Continue as normal
@@ -12,6 +14,18 @@ pub enum Trans {
     /// Remove the state at the top of the stack
     Pop,
 
+    /// Remove the state at the top of the stack and pass a value to the on_uncover_with method of the state below it
+    PopWith(Box<dyn Any>),
+
+    /// Remove the given number of states from the top of the stack, only the state that ends up at the top gets on_uncover called
+    PopN(usize),
+
+    /// Remove states from the top of the stack until the state at the top has the given get_name
+    PopTo(String),
+
+    /// Remove states from the top of the stack until the get_name of the state at the top matches the predicate
+    PopUntil(Box<dyn Fn(&str) -> bool>),
+
     /// Set the the state at the top of the stack to the given state
     Switch(Box<dyn State>),
 
@@ -21,9 +35,25 @@ pub enum Trans {
     /// Replaces the stack with the given stack
     NewStack(Vec<Box<dyn State>>),
 
+    /// Replaces the stack with the given stack choosing how the old stack is torn down
+    NewStackWith(Vec<Box<dyn State>>, Teardown),
+
     /// Executes a sequence of StateTrans'
     Sequence(Vec<Trans>),
 
+    /// Moves or clones entities from the world of the state at the first stack index to the world of the state at the second stack index
+    Transfer(usize, usize, EntityTransfer),
+
+    /// Pushes the given state, carries the entities over from the state below it and then removes the state below it
+    SwitchCarrying(Box<dyn State>, EntityTransfer),
+
+    /// Pushes the loading state, loads the models in the background and inserts them into Models\<T>, then pops the loading state and runs the Trans.
+    /// Only one load can run at a time, a Trans::LoadThen sent while another is loading is rejected
+    LoadThen(Box<dyn State>, ModelLoads, Box<Trans>),
+
+    /// Runs the Trans on the named layer instead of the layer it was sent to
+    OnLayer(String, Box<Trans>),
+
     /// Quit out of the engine
     Quit,
 }
@@ -33,23 +63,188 @@ impl Debug for Trans {
         match self {
             Trans::None => f.write_str("None"),
             Trans::Pop => f.write_str("Pop"),
+            Trans::PopWith(_) => f.write_str("PopWith"),
+            Trans::PopN(count) => f.write_str(&format!("PopN {}", count)),
+            Trans::PopTo(name) => f.write_str(&format!("PopTo {}", name)),
+            Trans::PopUntil(_) => f.write_str("PopUntil"),
             Trans::Push(_) => f.write_str("Push"),
             Trans::Switch(_) => f.write_str("Switch"),
             Trans::Replace(_) => f.write_str("Replace"),
             Trans::NewStack(_) => f.write_str("NewStack"),
+            Trans::NewStackWith(_, teardown) => f.write_str(&format!("NewStackWith {:?}", teardown)),
             Trans::Sequence(sequence) => f.write_str(&format!("Sequence {:?}", sequence)),
+            Trans::Transfer(from, to, transfer) => f.write_str(&format!("Transfer {} entities from {} to {}", transfer.entities().len(), from, to)),
+            Trans::SwitchCarrying(_, transfer) => f.write_str(&format!("SwitchCarrying {} entities", transfer.entities().len())),
+            Trans::LoadThen(_, models, then) => f.write_str(&format!("LoadThen {} models {:?}", models.len(), then)),
+            Trans::OnLayer(layer, trans) => f.write_str(&format!("OnLayer {} {:?}", layer, trans)),
             Trans::Quit => f.write_str("Quit"),
         }
     }
 }
 
+/// How the old stack is removed when it gets replaced by Trans::Replace, Trans::NewStack or Trans::NewStackWith
+/// 
+/// Both modes call on_pop on every state from the top down and free the godot nodes of every state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Teardown {
+    /// States are popped without being uncovered, this is what Trans::Replace and Trans::NewStack use
+    Silent,
+
+    /// States are popped as if Trans::Pop was sent for each of them so every state below the top gets on_uncover before its on_pop
+    Graceful,
+}
+
+/// Decides what happens to the states below a state while it is on the stack
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoverPolicy {
+    /// Covered states stay visible and keep getting shadow_update, this is the default and is what a HUD overlay wants
+    KeepRunning,
+
+    /// Covered states are hidden but keep getting shadow_update
+    Hide,
+
+    /// Covered states stay visible but stop getting shadow_update and their godot nodes stop processing and playing animations
+    Freeze,
+
+    /// Covered states are hidden and frozen, this is what a full screen pause menu wants
+    HideAndFreeze,
+}
+
+impl Default for CoverPolicy {
+    fn default() -> Self {
+        CoverPolicy::KeepRunning
+    }
+}
+
+impl CoverPolicy {
+    pub fn hides(&self) -> bool {
+        match self {
+            CoverPolicy::Hide | CoverPolicy::HideAndFreeze => true,
+            _ => false,
+        }
+    }
+
+    pub fn freezes(&self) -> bool {
+        match self {
+            CoverPolicy::Freeze | CoverPolicy::HideAndFreeze => true,
+            _ => false,
+        }
+    }
+}
+
+type ScopeSwap = Box<dyn Fn(&mut Resources, &mut Resources, &mut Resources) -> bool>;
+
+/// Resources that belong to a single state and are layered over the engine's global Resources
+/// 
+/// While one of a state's methods or schedules is running its scoped resources are moved into the Resources that gets passed in,
+/// any global resource of the same type is hidden until the method returns. This means reads and writes through the Resources
+/// argument (and from systems) go to the state's own copy first.
+/// 
+/// Scoped resources are dropped when the state is popped.
+/// ```
+/// fn on_push(&mut self, data: &mut StateData, resources: &mut Resources) {
+///     // Only this state will see this TextResource
+///     data.resources.insert(TextResource { display: self.output.clone(), input: "".into() });
+/// }
+/// ```
+/// -- NOTE: A resource inserted into data.resources while one of the state's methods is running is seen through the Resources argument
+/// from the next time the state is run. While a method is running use the Resources argument to read scoped resources.
+#[derive(Default)]
+pub struct StateResources {
+    local: Resources,
+    shadowed: Resources,
+    swaps: HashMap<TypeId, (ScopeSwap, ScopeSwap)>,
+    active: Vec<TypeId>,
+}
+
+impl StateResources {
+    /// Inserts a resource into this state's scope, replacing any scoped resource of the same type
+    pub fn insert<R>(&mut self, value: R)
+        where R: 'static + Send + Sync {
+        self.swaps.entry(TypeId::of::<R>()).or_insert_with(|| {
+            let enter: ScopeSwap = Box::new(|global, local, shadowed| {
+                match local.remove::<R>() {
+                    Some(value) => {
+                        if let Some(hidden) = global.remove::<R>() {
+                            shadowed.insert(hidden);
+                        }
+                        global.insert(value);
+                        true
+                    },
+                    None => false,
+                }
+            });
+            let exit: ScopeSwap = Box::new(|global, local, shadowed| {
+                if let Some(value) = global.remove::<R>() {
+                    local.insert(value);
+                }
+                if let Some(hidden) = shadowed.remove::<R>() {
+                    global.insert(hidden);
+                }
+                true
+            });
+            (enter, exit)
+        });
+        self.local.insert(value);
+    }
+
+    pub fn remove<R>(&mut self) -> Option<R>
+        where R: 'static + Send + Sync {
+        self.local.remove::<R>()
+    }
+
+    pub fn get<R>(&self) -> Option<Fetch<'_, R>>
+        where R: 'static + Send + Sync {
+        self.local.get::<R>()
+    }
+
+    pub fn get_mut<R>(&self) -> Option<FetchMut<'_, R>>
+        where R: 'static + Send + Sync {
+        self.local.get_mut::<R>()
+    }
+
+    /// Returns true if this state has ever inserted a resource of this type into its scope
+    pub fn is_scoped<R>(&self) -> bool
+        where R: 'static + Send + Sync {
+        self.swaps.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Moves the scoped resources into the global resources
+    pub(crate) fn enter(&mut self, global: &mut Resources) {
+        for (type_id, (enter, _)) in self.swaps.iter() {
+            if enter(global, &mut self.local, &mut self.shadowed) {
+                self.active.push(*type_id);
+            }
+        }
+    }
+
+    /// Moves the scoped resources back out of the global resources and restores any global resources they were hiding
+    pub(crate) fn exit(&mut self, global: &mut Resources) {
+        for type_id in self.active.drain(..) {
+            if let Some((_, exit)) = self.swaps.get(&type_id) {
+                exit(global, &mut self.local, &mut self.shadowed);
+            }
+        }
+    }
+}
+
 pub struct StateData {
+    pub(crate) schedule: Option<StateSchedule>,
+    pub(crate) shadow_schedule: Option<StateSchedule>,
     pub(crate) receiver: crossbeam_channel::Receiver<legion::event::Event>,
-    pub(crate) rootnode: Option<Node>,
-    pub(crate) containernode: Option<Node>,
-    pub statenode: Option<Node>,
-    pub(crate) node_lookup: HashMap<Entity, Node>,
+    pub(crate) rootnode: Option<SceneNode>,
+    pub(crate) containernode: Option<SceneNode>,
+    pub statenode: Option<SceneNode>,
+    pub(crate) node_lookup: HashMap<Entity, SceneNode>,
+    pub(crate) name: String,
+    pub(crate) elapsed: f64,
+    pub(crate) cover_policy: CoverPolicy,
+    pub(crate) sync_when_covered: bool,
+    pub(crate) blocks_input: bool,
+    pub(crate) hidden: bool,
+    pub(crate) frozen: bool,
     pub world: LWorld,
+    pub resources: StateResources,
 }
 
 impl<'a> StateData {
@@ -58,13 +253,99 @@ impl<'a> StateData {
         world.subscribe(sender, any());
         StateData { 
             world,
+            resources: StateResources::default(),
+            schedule: None,
+            shadow_schedule: None,
             receiver,
             rootnode: None,
             containernode: None,
             statenode: None,
             node_lookup: HashMap::new(),
+            name: String::new(),
+            elapsed: 0f64,
+            cover_policy: CoverPolicy::default(),
+            sync_when_covered: true,
+            blocks_input: false,
+            hidden: false,
+            frozen: false,
         }
     }
+
+    /// Layers this state's scoped resources over the global resources for the duration of f
+    pub(crate) fn scoped<F, R>(&mut self, resources: &mut Resources, f: F) -> R
+        where F: FnOnce(&mut StateData, &mut Resources) -> R {
+        self.resources.enter(resources);
+        let result = f(self, resources);
+        self.resources.exit(resources);
+        result
+    }
+
+    /// The get_name of the state this data belongs to
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns true if a state above this one has hidden it
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    /// Returns true if a state above this one has frozen it, frozen states do not get shadow_update
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// The total of every scaled delta this state has been updated with.
+    /// This only advances while the state is at the top of the stack so it stops while the state is covered.
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+}
+
+/// A set of systems that the engine runs on a state's world after update or shadow_update
+/// 
+/// Example of declaring a schedule on a state:
+/// ```
+/// fn schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> {
+///     Some(StateSchedule::new(vec![
+///         input_system(),
+///         move_system(),
+///     ]))
+/// }
+/// ```
+pub struct StateSchedule {
+    schedule: Schedule,
+    system_names: Vec<String>,
+}
+
+impl StateSchedule {
+    /// Creates a legion Schedule from the systems, systems are run in parallel wherever their component and resource access allows it
+    pub fn new(systems: Vec<Box<dyn Schedulable>>) -> Self {
+        let system_names = systems.iter().map(|system| system.name().to_string()).collect();
+        let schedule = systems.into_iter()
+            .fold(Schedule::builder(), |builder, system| builder.add_system(system))
+            .flush()
+            .build();
+
+        StateSchedule {
+            schedule,
+            system_names,
+        }
+    }
+
+    pub fn system_names(&self) -> &Vec<String> {
+        &self.system_names
+    }
+
+    pub(crate) fn execute(&mut self, world: &mut LWorld, resources: &mut Resources) {
+        self.schedule.execute(world, resources);
+    }
+}
+
+impl Debug for StateSchedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(&format!("{:?}", self.system_names))
+    }
 }
 
 pub trait State {
@@ -73,7 +354,176 @@ pub trait State {
     fn on_pop(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
     fn on_cover(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
     fn on_uncover(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
+    /// Called instead of on_uncover when the state above this one was popped with Trans::PopWith, by default this drops the result and calls on_uncover
+    /// ```
+    /// fn on_uncover_with(&mut self, data: &mut StateData, resources: &mut Resources, result: Box<dyn Any>) {
+    ///     if let Ok(confirmed) = result.downcast::<bool>() {
+    ///         // --snip
+    ///     }
+    /// }
+    /// ```
+    fn on_uncover_with(&mut self, data: &mut StateData, resources: &mut Resources, _result: Box<dyn Any>) { self.on_uncover(data, resources) }
     fn update(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
     fn shadow_update(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
+    fn on_quit(&mut self, _data: &mut StateData, _resources: &mut Resources) { }
+    fn on_quit_request(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Trans { Trans::Quit }
+    /// The systems the engine runs after update while this state is at the top of the stack, this is called once when the state is pushed
+    fn schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> { None }
+    /// The systems the engine runs after shadow_update while this state is covered, this is called once when the state is pushed
+    fn shadow_schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> { None }
+    /// Called on the loading state of a Trans::LoadThen when a model fails to load, the Trans after the load will not be run.
+    /// The returned Trans is run on the loading state's layer, by default this pops the loading state. To retry the load instead:
+    /// ```
+    /// fn on_load_error(&mut self, data: &mut StateData, resources: &mut Resources, error: &LoadError) -> Trans {
+    ///     Trans::Sequence(vec![Trans::Pop, Trans::LoadThen(Box::new(LoadingState { }), level_models().into(), Box::new(Trans::Push(Box::new(LevelState { }))))])
+    /// }
+    /// ```
+    fn on_load_error(&mut self, _data: &mut StateData, _resources: &mut Resources, _error: &LoadError) -> Trans { Trans::Pop }
+    /// Called after entities have been moved or cloned into this state's world, entities are the new entities in this world
+    /// ```
+    /// fn on_transfer_in(&mut self, data: &mut StateData, resources: &mut Resources, entities: &[Entity]) {
+    ///     for entity in entities {
+    ///         if let Some(mut pos) = data.world.get_component_mut::<Position>(*entity) {
+    ///             *pos = Position::new(100f32, 100f32);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn on_transfer_in(&mut self, _data: &mut StateData, _resources: &mut Resources, _entities: &[Entity]) { }
+    /// What happens to the states below this one while it is on the stack, this is called once when the state is pushed.
+    /// If several states are stacked the covered state is hidden or frozen if any state above it hides or freezes.
+    fn cover_policy(&mut self, _data: &mut StateData, _resources: &mut Resources) -> CoverPolicy { CoverPolicy::KeepRunning }
+    /// Whether this state's entities keep getting synced to godot while it is covered and visible, this is called once when the state is pushed.
+    /// Returning false saves the cost of syncing a state whose entities don't change while it is covered, removed entities still have their nodes freed.
+    fn sync_when_covered(&mut self, _data: &mut StateData, _resources: &mut Resources) -> bool { true }
+    /// Whether this state consumes all input after it is updated so that the states below it never see any, this is called once when the state is pushed.
+    /// This is what a modal menu wants, see InputState for consuming single actions instead.
+    fn blocks_input(&mut self, _data: &mut StateData, _resources: &mut Resources) -> bool { false }
     fn get_name(&mut self, _data: &mut StateData, _resources: &mut Resources) -> String { String::from("UnnamedState") }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::harness::tests::*;
+
+    struct Updates(u32);
+    struct ShadowUpdates(u32);
+
+    fn count_updates() -> Box<dyn Schedulable> {
+        SystemBuilder::<()>::new("CountUpdates")
+            .with_query(<Write<Updates>>::query())
+            .build(|_, world, _, query| {
+                for mut updates in query.iter_mut(&mut *world) {
+                    updates.0 += 1;
+                }
+            })
+    }
+
+    fn count_shadow_updates() -> Box<dyn Schedulable> {
+        SystemBuilder::<()>::new("CountShadowUpdates")
+            .with_query(<Write<ShadowUpdates>>::query())
+            .build(|_, world, _, query| {
+                for mut updates in query.iter_mut(&mut *world) {
+                    updates.0 += 1;
+                }
+            })
+    }
+
+    /// Counts how often each of its schedules ran on an entity in its world
+    struct ScheduledState;
+
+    impl State for ScheduledState {
+        fn on_push(&mut self, data: &mut StateData, _resources: &mut Resources) {
+            data.world.insert((), vec![(Updates(0), ShadowUpdates(0))]);
+        }
+        fn schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> {
+            Some(StateSchedule::new(vec![count_updates()]))
+        }
+        fn shadow_schedule(&mut self, _data: &mut StateData, _resources: &mut Resources) -> Option<StateSchedule> {
+            Some(StateSchedule::new(vec![count_shadow_updates()]))
+        }
+    }
+
+    fn counts(harness: &TestHarness<i32>) -> (u32, u32) {
+        let updates = <Read<Updates>>::query().iter(harness.world(0)).next().unwrap().0;
+        let shadow_updates = <Read<ShadowUpdates>>::query().iter(harness.world(0)).next().unwrap().0;
+        (updates, shadow_updates)
+    }
+
+    #[test]
+    fn the_schedule_runs_while_the_state_is_on_top() {
+        let (mut harness, _) = harness();
+        harness.push(Box::new(ScheduledState));
+        assert_eq!(harness.state_data(0).schedule.as_ref().unwrap().system_names(), &vec!["CountUpdates".to_string()]);
+
+        harness.step_n(2);
+        assert_eq!(counts(&harness), (2, 0));
+    }
+
+    #[test]
+    fn the_shadow_schedule_runs_while_the_state_is_covered() {
+        let (mut harness, log) = harness();
+        harness.push(Box::new(ScheduledState));
+        harness.step_n(2);
+
+        harness.push(LogState::new("Hud", &log));
+        harness.step_n(3);
+        assert_eq!(counts(&harness), (2, 3));
+    }
+
+    struct Counter(u32);
+
+    /// Counts its updates and shadow updates in a scoped resource
+    struct CountingState;
+
+    impl State for CountingState {
+        fn on_push(&mut self, data: &mut StateData, _resources: &mut Resources) {
+            data.resources.insert(Counter(0));
+        }
+        fn update(&mut self, _data: &mut StateData, resources: &mut Resources) {
+            resources.get_mut::<Counter>().unwrap().0 += 1;
+        }
+        fn shadow_update(&mut self, _data: &mut StateData, resources: &mut Resources) {
+            resources.get_mut::<Counter>().unwrap().0 += 10;
+        }
+    }
+
+    fn scoped_count(harness: &TestHarness<i32>, index: usize) -> u32 {
+        harness.state_data(index).resources.get::<Counter>().unwrap().0
+    }
+
+    #[test]
+    fn scoped_resources_are_only_seen_by_their_state() {
+        let (mut harness, _) = harness();
+        harness.push(Box::new(CountingState));
+        harness.step_n(3);
+
+        assert_eq!(scoped_count(&harness, 0), 3);
+        assert!(harness.state_data(0).resources.is_scoped::<Counter>());
+        assert!(harness.resources().get::<Counter>().is_none());
+    }
+
+    #[test]
+    fn scoped_resources_hide_the_global_resource_of_the_same_type() {
+        let (mut harness, _) = harness();
+        harness.resources().insert(Counter(100));
+        harness.push(Box::new(CountingState)).push(Box::new(CountingState));
+        harness.step_n(2);
+
+        assert_eq!(scoped_count(&harness, 0), 20);
+        assert_eq!(scoped_count(&harness, 1), 2);
+        assert_eq!(harness.resources().get::<Counter>().unwrap().0, 100);
+    }
+
+    #[test]
+    fn scoped_resources_are_dropped_with_their_state() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log)).push(Box::new(CountingState));
+        harness.step();
+
+        send(&mut harness, || Trans::Pop);
+        harness.step();
+        assert!(harness.resources().get::<Counter>().is_none());
+    }
 }
\ No newline at end of file
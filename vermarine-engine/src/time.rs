@@ -0,0 +1,133 @@
+/// The Time resource is updated by the engine at the start of every frame
+///
+/// Example of moving an entity at 400 pixels per second inside of a system:
+/// ```
+/// SystemBuilder::<()>::new("MoveSystem")
+///     .read_resource::<Time>()
+///     .with_query(<(Write<Position>, Read<Velocity>)>::query())
+///     .build(move |commands, world, time, query| {
+///         let delta = time.delta() as f32;
+///         for (mut pos, vel) in query.iter_mut(&mut *world) {
+///             pos.x += vel.x * 400f32 * delta;
+///         }
+///     })
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Time {
+    delta: f64,
+    unscaled_delta: f64,
+    elapsed: f64,
+    unscaled_elapsed: f64,
+    frame: u64,
+    time_scale: f64,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Time {
+            delta: 0f64,
+            unscaled_delta: 0f64,
+            elapsed: 0f64,
+            unscaled_elapsed: 0f64,
+            frame: 0,
+            time_scale: 1f64,
+        }
+    }
+}
+
+impl Time {
+    /// The seconds since the last frame multiplied by the time scale
+    pub fn delta(&self) -> f64 {
+        self.delta
+    }
+
+    /// The seconds since the last frame as given to _physics_process
+    pub fn unscaled_delta(&self) -> f64 {
+        self.unscaled_delta
+    }
+
+    /// The total of every scaled delta since the engine started
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// The total of every unscaled delta since the engine started
+    pub fn unscaled_elapsed(&self) -> f64 {
+        self.unscaled_elapsed
+    }
+
+    /// The number of frames that have been run including the current one
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets the multiplier applied to delta from the next frame onwards, 0 stops time and 0.5 runs at half speed
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0f64);
+    }
+
+    pub(crate) fn advance(&mut self, unscaled_delta: f64) {
+        self.unscaled_delta = unscaled_delta;
+        self.delta = unscaled_delta * self.time_scale;
+        self.unscaled_elapsed += self.unscaled_delta;
+        self.elapsed += self.delta;
+        self.frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+    use crate::harness::tests::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn steps_advance_time_by_the_delta() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.step_n(3);
+
+        let time = *harness.resources().get::<Time>().unwrap();
+        assert_eq!(time.frame(), 3);
+        assert!(close(time.delta(), 1f64 / 60f64));
+        assert!(close(time.elapsed(), 3f64 / 60f64));
+        assert!(close(time.unscaled_elapsed(), 3f64 / 60f64));
+    }
+
+    #[test]
+    fn the_time_scale_only_changes_the_scaled_values() {
+        let mut time = Time::default();
+        time.advance(1f64);
+        time.set_time_scale(0.5f64);
+        time.advance(1f64);
+
+        assert!(close(time.delta(), 0.5f64));
+        assert!(close(time.unscaled_delta(), 1f64));
+        assert!(close(time.elapsed(), 1.5f64));
+        assert!(close(time.unscaled_elapsed(), 2f64));
+
+        time.set_time_scale(-1f64);
+        assert!(close(time.time_scale(), 0f64));
+    }
+
+    #[test]
+    fn states_get_the_scaled_delta_and_only_age_while_on_top() {
+        let (mut harness, log) = harness();
+        harness.push(LogState::new("A", &log));
+        harness.resources().get_mut::<Time>().unwrap().set_time_scale(2f64);
+        harness.step_n(3);
+        assert!(close(harness.state_data(0).elapsed(), 6f64 / 60f64));
+
+        harness.push(LogState::new("B", &log));
+        harness.step_n(3);
+        assert!(close(harness.state_data(0).elapsed(), 6f64 / 60f64));
+        assert!(close(harness.state_data(1).elapsed(), 6f64 / 60f64));
+    }
+}
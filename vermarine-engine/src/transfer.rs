@@ -0,0 +1,188 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+type CopyComponent = Box<dyn Fn(&LWorld, Entity, &mut LWorld, Entity)>;
+
+/// Marks an entity that was moved or cloned into a state's world by an EntityTransfer, from is the entity in the old world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transferred {
+    pub from: Entity,
+}
+
+/// Moves or clones entities from the world of one state to the world of another.
+///
+/// Only the component types registered with with() are carried over, the Renderable is always carried over
+/// along with the Position or Position3D that places it so that it shows up where it was.
+/// When moving, the entity's existing nodes are reparented under the RenderablesContainer of the new state instead of being instanced again.
+/// When cloning, the Renderable is copied without its nodes and gets instanced fresh in the new state.
+///
+/// Example of carrying the player into the next level:
+/// ```
+/// let transfer = EntityTransfer::new(vec![player])
+///     .with::<TakesInput>()
+///     .with::<Velocity>();
+/// sender.send(move || Trans::SwitchCarrying(Box::new(LevelState { level: 2 }), transfer)).unwrap();
+/// ```
+pub struct EntityTransfer {
+    entities: Vec<Entity>,
+    components: Vec<CopyComponent>,
+    clone: bool,
+}
+
+impl EntityTransfer {
+    pub fn new(entities: Vec<Entity>) -> Self {
+        EntityTransfer {
+            entities,
+            components: vec![],
+            clone: false,
+        }
+    }
+
+    /// Registers a component type to be carried over with the entities, entities that don't have the component are skipped
+    pub fn with<C>(mut self) -> Self
+        where C: Clone + Send + Sync + 'static {
+        self.components.push(Box::new(|src: &LWorld, from: Entity, dst: &mut LWorld, to: Entity| {
+            let component = src.get_component::<C>(from).map(|component| (*component).clone());
+            if let Some(component) = component {
+                let _ = dst.add_component(to, component);
+            }
+        }));
+        self
+    }
+
+    /// Leaves the entities in the old world and creates copies of them in the new world
+    pub fn cloned(mut self) -> Self {
+        self.clone = true;
+        self
+    }
+
+    pub fn entities(&self) -> &Vec<Entity> {
+        &self.entities
+    }
+
+    pub fn is_clone(&self) -> bool {
+        self.clone
+    }
+
+    /// Runs the transfer, returns the new entities in the same order they were given in. Entities that no longer exist are skipped
+    pub(crate) fn run(&self, backend: &mut dyn SceneBackend, src: &mut StateData, dst: &mut StateData) -> Vec<Entity> {
+        let mut transferred = vec![];
+        let mut new_entities = HashMap::new();
+
+        for &from in self.entities.iter() {
+            if !src.world.is_alive(from) {
+                continue;
+            }
+
+            let to = dst.world.insert((), vec![(Transferred { from },)])[0];
+            for copy in self.components.iter() {
+                copy(&src.world, from, &mut dst.world, to);
+            }
+
+            let renderable = if self.clone {
+                src.world.get_component::<Renderable>(from).map(|renderable| renderable.detached_clone())
+            } else {
+                src.world.get_component_mut::<Renderable>(from).map(|mut renderable| std::mem::replace(&mut *renderable, Renderable::default()))
+            };
+            let position = src.world.get_component::<Position>(from).map(|position| *position);
+            let position_3d = src.world.get_component::<Position3D>(from).map(|position| *position);
+
+            if !self.clone {
+                // Take the nodes out of the old state before deleting the entity so that they don't get freed
+                src.node_lookup.remove(&from);
+                if let (Some(container), Some(node)) = (dst.containernode, renderable.as_ref().and_then(|renderable| renderable.container_node)) {
+                    backend.reparent(node, container);
+                }
+                src.world.delete(from);
+            }
+
+            if let Some(mut renderable) = renderable {
+                renderable.nested_in = None;
+                let _ = dst.world.add_component(to, renderable);
+                if let Some(position) = position {
+                    let _ = dst.world.add_component(to, position);
+                }
+                if let Some(position_3d) = position_3d {
+                    let _ = dst.world.add_component(to, position_3d);
+                }
+            }
+            new_entities.insert(from, to);
+            transferred.push(to);
+        }
+
+        // Children that were carried over with their parent keep it
+        for &to in transferred.iter() {
+            if let Some(mut parent) = dst.world.get_component_mut::<Parent>(to) {
+                if let Some(new_parent) = new_entities.get(&parent.entity) {
+                    parent.entity = *new_parent;
+                }
+            }
+        }
+
+        transferred
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    struct EmptyState;
+
+    impl State for EmptyState {}
+
+    fn square() -> Renderable {
+        Renderable::new(Position::default(), 0, Template::None)
+    }
+
+    fn two_state_harness() -> TestHarness<i32> {
+        let mut models = Models::<i32>::default();
+        models.insert_headless(Some("Square"), None, Template::None);
+        let mut harness = TestHarness::new(models);
+        harness.push(Box::new(EmptyState)).push(Box::new(EmptyState));
+        harness
+    }
+
+    #[test]
+    fn moved_entities_keep_their_nodes_and_parents() {
+        let mut harness = two_state_harness();
+        let parent = harness.world_mut(0).insert((), vec![(square(), Position::new(1f32, 2f32))])[0];
+        let child = harness.world_mut(0).insert((), vec![(square(), Position::default(), Parent { entity: parent })])[0];
+        harness.step();
+        let node = harness.world(0).get_component::<Renderable>(parent).unwrap().container_node;
+
+        let transfer = EntityTransfer::new(vec![parent, child]).with::<Parent>();
+        let moved = harness.engine_mut().transfer(0, 1, transfer);
+        assert_eq!(moved.len(), 2);
+        assert!(!harness.world(0).is_alive(parent) && !harness.world(0).is_alive(child));
+
+        let world = harness.world(1);
+        assert_eq!(world.get_component::<Parent>(moved[1]).unwrap().entity, moved[0]);
+        assert_eq!(world.get_component::<Transferred>(moved[0]).unwrap().from, parent);
+        assert_eq!(world.get_component::<Renderable>(moved[0]).unwrap().container_node, node);
+
+        harness.step();
+        let container = |entity: Entity| harness.world(1).get_component::<Renderable>(entity).unwrap().container_node.unwrap();
+        assert_eq!(harness.tree().get_node(container(moved[0])).unwrap().parent, harness.container(1));
+        assert_eq!(harness.tree().get_node(container(moved[1])).unwrap().parent, container(moved[0]).headless());
+        assert_eq!(harness.renderable_count(0), 0);
+    }
+
+    #[test]
+    fn renderables_are_carried_with_their_position() {
+        let mut harness = two_state_harness();
+        let entity = harness.world_mut(0).insert((), vec![(square(), Position::new(1f32, 2f32))])[0];
+        let marker = harness.world_mut(0).insert((), vec![(Position::new(3f32, 4f32),)])[0];
+        harness.step();
+
+        let cloned = harness.engine_mut().transfer(0, 1, EntityTransfer::new(vec![entity, marker]).cloned());
+        harness.step();
+        assert!(harness.world(0).is_alive(entity));
+        assert_eq!(harness.renderable_count(0), 1);
+        assert_eq!(harness.renderable_count(1), 1);
+        assert_eq!(*harness.world(1).get_component::<Position>(cloned[0]).unwrap(), Position::new(1f32, 2f32));
+
+        // Without a Renderable the Position is only carried when it is registered with with()
+        assert!(harness.world(1).get_component::<Position>(cloned[1]).is_none());
+    }
+}